@@ -14,17 +14,232 @@ CREATE TABLE items (
     title TEXT NOT NULL,
     html TEXT NOT NULL,
     markdown TEXT NOT NULL,
-    discussed_on DATE DEFAULT NULL
+    discussed_on DATE DEFAULT NULL,
+    status TEXT NOT NULL DEFAULT 'open',
+    proposed_by INTEGER DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    authors TEXT DEFAULT NULL,
+    year INTEGER DEFAULT NULL,
+    venue TEXT DEFAULT NULL,
+    doi TEXT DEFAULT NULL,
+    arxiv_id TEXT DEFAULT NULL,
+    url TEXT DEFAULT NULL,
+
+    FOREIGN KEY(proposed_by) REFERENCES users(id) ON DELETE SET NULL
+);
+CREATE INDEX item_status ON items(status);
+CREATE INDEX item_proposer ON items(proposed_by);
+
+-- full-text index of the items, kept up to date by the triggers below
+DROP TABLE IF EXISTS items_fts;
+CREATE VIRTUAL TABLE items_fts USING fts5(
+    title, markdown, authors, venue,
+    content='items', content_rowid='id',
+    tokenize='porter unicode61 remove_diacritics 2'
+);
+INSERT INTO items_fts(items_fts) VALUES('rebuild');
+CREATE TRIGGER items_fts_insert AFTER INSERT ON items BEGIN
+    INSERT INTO items_fts(rowid, title, markdown, authors, venue)
+    VALUES (new.id, new.title, new.markdown, new.authors, new.venue);
+END;
+CREATE TRIGGER items_fts_delete AFTER DELETE ON items BEGIN
+    INSERT INTO items_fts(items_fts, rowid, title, markdown, authors, venue)
+    VALUES ('delete', old.id, old.title, old.markdown, old.authors, old.venue);
+END;
+CREATE TRIGGER items_fts_update AFTER UPDATE OF title, markdown, authors, venue ON items BEGIN
+    INSERT INTO items_fts(items_fts, rowid, title, markdown, authors, venue)
+    VALUES ('delete', old.id, old.title, old.markdown, old.authors, old.venue);
+    INSERT INTO items_fts(rowid, title, markdown, authors, venue)
+    VALUES (new.id, new.title, new.markdown, new.authors, new.venue);
+END;
+
+DROP TABLE IF EXISTS status_changes;
+CREATE TABLE status_changes (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    old_status TEXT NOT NULL,
+    new_status TEXT NOT NULL,
+    changed_at TIMESTAMP NOT NULL,
+
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+);
+CREATE INDEX item_status_changes ON status_changes(item_id, changed_at);
+
+DROP TABLE IF EXISTS revisions;
+CREATE TABLE revisions (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    markdown TEXT NOT NULL,
+    html TEXT NOT NULL,
+    edited_by INTEGER DEFAULT NULL,
+    edited_at TIMESTAMP NOT NULL,
+    authors TEXT DEFAULT NULL,
+    year INTEGER DEFAULT NULL,
+    venue TEXT DEFAULT NULL,
+    doi TEXT DEFAULT NULL,
+    arxiv_id TEXT DEFAULT NULL,
+    url TEXT DEFAULT NULL,
+
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+    FOREIGN KEY(edited_by) REFERENCES users(id) ON DELETE SET NULL
+);
+CREATE INDEX item_revisions ON revisions(item_id, id);
+
+DROP TABLE IF EXISTS comments;
+CREATE TABLE comments (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    markdown TEXT NOT NULL,
+    html TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    edited_at TIMESTAMP DEFAULT NULL,
+
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX item_comments ON comments(item_id, created_at);
+
+DROP TABLE IF EXISTS meeting_notes;
+CREATE TABLE meeting_notes (
+    item_id INTEGER PRIMARY KEY,
+    minutes_markdown TEXT NOT NULL,
+    minutes_html TEXT NOT NULL,
+    slides TEXT NOT NULL,
+    updated_by INTEGER DEFAULT NULL,
+    updated_at TIMESTAMP NOT NULL,
+
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+    FOREIGN KEY(updated_by) REFERENCES users(id) ON DELETE SET NULL
 );
 
+DROP TABLE IF EXISTS attachments;
+CREATE TABLE attachments (
+    id INTEGER PRIMARY KEY,
+    item_id INTEGER NOT NULL,
+    filename TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    uploaded_by INTEGER DEFAULT NULL,
+    uploaded_at TIMESTAMP NOT NULL,
+
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+    FOREIGN KEY(uploaded_by) REFERENCES users(id) ON DELETE SET NULL
+);
+CREATE INDEX item_attachments ON attachments(item_id, uploaded_at);
+
+DROP TABLE IF EXISTS ratings;
+CREATE TABLE ratings (
+    user_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    rating INTEGER NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    rated_at TIMESTAMP NOT NULL,
+
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+);
+CREATE UNIQUE INDEX no_dup_ratings ON ratings(user_id, item_id);
+
 DROP TABLE IF EXISTS votes;
 CREATE TABLE votes (
     user_id INTEGER NOT NULL,
     item_id INTEGER NOT NULL,
     ordinal INTEGER NOT NULL,
+    voted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
 
     FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
     FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
 );
 CREATE UNIQUE INDEX no_dup_votes ON votes(user_id, item_id);
 CREATE INDEX ballot ON votes(user_id ASC, ordinal ASC);
+
+DROP TABLE IF EXISTS notifications;
+CREATE TABLE notifications (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    item_id INTEGER DEFAULT NULL,
+    message TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    is_read BOOL NOT NULL DEFAULT false,
+
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE SET NULL
+);
+CREATE INDEX user_notifications ON notifications(user_id, created_at);
+
+DROP TABLE IF EXISTS tags;
+CREATE TABLE tags (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+DROP TABLE IF EXISTS item_tags;
+CREATE TABLE item_tags (
+    item_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+
+    PRIMARY KEY(item_id, tag_id)
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+    FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+CREATE INDEX tag_items ON item_tags(tag_id);
+
+DROP TABLE IF EXISTS endorsements;
+CREATE TABLE endorsements (
+    user_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    endorsed_at TIMESTAMP NOT NULL,
+
+    PRIMARY KEY(user_id, item_id)
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+);
+CREATE INDEX item_endorsements ON endorsements(item_id);
+
+DROP TABLE IF EXISTS volunteers;
+CREATE TABLE volunteers (
+    user_id INTEGER NOT NULL,
+    item_id INTEGER NOT NULL,
+    volunteered_at TIMESTAMP NOT NULL,
+
+    PRIMARY KEY(user_id, item_id)
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+);
+CREATE INDEX item_volunteers ON volunteers(item_id);
+
+DROP TABLE IF EXISTS presenters;
+CREATE TABLE presenters (
+    item_id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    assigned_at TIMESTAMP NOT NULL,
+
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX user_presentations ON presenters(user_id);
+
+DROP TABLE IF EXISTS attendance;
+CREATE TABLE attendance (
+    item_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    checked_in_at TIMESTAMP NOT NULL,
+    checked_in_by INTEGER DEFAULT NULL,
+
+    PRIMARY KEY(item_id, user_id)
+    FOREIGN KEY(item_id) REFERENCES items(id) ON DELETE CASCADE
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    FOREIGN KEY(checked_in_by) REFERENCES users(id) ON DELETE SET NULL
+);
+CREATE INDEX user_attendance ON attendance(user_id);
+
+DROP TABLE IF EXISTS ballots;
+CREATE TABLE ballots (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    submitted_at TIMESTAMP NOT NULL,
+
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+CREATE INDEX ballot_submissions ON ballots(submitted_at);
@@ -1,8 +1,15 @@
 use prank::MailConfig;
 use rocket::serde::Serialize;
 
-use crate::{DbConn, Item, User, Vote};
+use crate::{
+    chart, ActivityStats, Attachment, Attendance, AttendanceStats, Attendee, Comment, DbConn,
+    DiffLine, Duplicate, Endorsement, EndorsementConfig, FairnessConfig, Item, ItemStatus,
+    MeetingNotes, NewItemData, Notification, Presenter, PresenterStats, RatingSummary,
+    Recommendation, Recommendations, Revision, SearchHit, SearchQuery, StatusChange, Tag,
+    TrackConfig, TrackWinner, User, Volunteer, Vote,
+};
 
+use chrono::{NaiveDate, Utc};
 use std::collections::HashMap;
 pub type Empty = HashMap<i32, i32>;
 
@@ -10,17 +17,46 @@ pub type Empty = HashMap<i32, i32>;
 #[serde(crate = "rocket::serde")]
 struct Context {
     flash: Option<(String, String)>,
+    user_id: Option<i32>,
     username: Option<String>,
     is_admin: Option<bool>,
+    /// number of unread notifications, only counted on some pages
+    unread: i64,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct VoteContext {
     next: Option<Item>,
+    next_presenter: Option<String>,
+    /// whether the user checked in for the meeting of `next`
+    checked_in: bool,
+    today: NaiveDate,
     winner: Option<Item>,
+    /// see `Election::transcript`
+    transcript: Vec<String>,
     second: Option<Item>,
+    /// winners of the configured tracks
+    tracks: Vec<TrackWinner>,
     items: Vec<(Item, Option<i32>)>,
+    /// unranked items which similar voters ranked highly
+    recommendations: Vec<(Item, Recommendation)>,
+    /// whether the recommendations are based on similar voters or on all voters
+    personalized: bool,
+    /// open items with too few endorsements to be on the ballot
+    unendorsed: Vec<Item>,
+    endorsements: HashMap<String, i64>,
+    /// ids of the items endorsed by the user
+    endorsed: Vec<i32>,
+    endorsements_to_rank: u32,
+    /// volunteers of the items and the next item
+    volunteers: HashMap<String, Vec<Volunteer>>,
+    comments: HashMap<String, Vec<Comment>>,
+    attachments: HashMap<String, Vec<Attachment>>,
+    tags: HashMap<String, Vec<String>>,
+    all_tags: Vec<String>,
+    /// items without this tag are hidden
+    tag: Option<String>,
     context: Context,
 }
 
@@ -28,6 +64,22 @@ pub struct VoteContext {
 #[serde(crate = "rocket::serde")]
 pub struct ItemContext {
     items: Vec<Item>,
+    transitions: HashMap<ItemStatus, &'static [ItemStatus]>,
+    comments: HashMap<String, Vec<Comment>>,
+    attachments: HashMap<String, Vec<Attachment>>,
+    notes: HashMap<String, MeetingNotes>,
+    ratings: HashMap<String, RatingSummary>,
+    my_ratings: HashMap<String, i32>,
+    presenters: HashMap<String, String>,
+    volunteers: HashMap<String, Vec<Volunteer>>,
+    attendance: HashMap<String, Vec<Attendee>>,
+    /// id and name of all approved users, only for admins to check them in
+    members: Vec<(i32, String)>,
+    today: NaiveDate,
+    tags: HashMap<String, Vec<String>>,
+    all_tags: Vec<String>,
+    /// only items with this tag are listed
+    tag: Option<String>,
     context: Context,
 }
 
@@ -37,10 +89,95 @@ pub struct UserContext {
     context: Context,
 }
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DraftContext {
+    draft: NewItemData,
+    duplicates: Vec<Duplicate>,
+    context: Context,
+}
+
+/// Entry of an uploaded bibliography awaiting confirmation.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ImportPreview {
+    pub item: NewItemData,
+    pub duplicates: Vec<Duplicate>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ImportContext {
+    entries: Vec<ImportPreview>,
+    context: Context,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct EditContext {
     item: Option<Item>,
+    tags: Vec<String>,
+    status_changes: Vec<StatusChange>,
+    context: Context,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct RevisionContext {
+    item: Item,
+    revisions: Vec<Revision>,
+    from: Option<Revision>,
+    to: Option<Revision>,
+    diff: Vec<DiffLine>,
+    context: Context,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SearchContext {
+    query: SearchQuery,
+    statuses: &'static [ItemStatus],
+    hits: Vec<SearchHit>,
+    context: Context,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct NotificationContext {
+    notifications: Vec<Notification>,
+    items: HashMap<String, Item>,
+    context: Context,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PresenterContext {
+    stats: Vec<PresenterStats>,
+    context: Context,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct StatsContext {
+    stats: ActivityStats,
+    ballots_chart: String,
+    voters_chart: String,
+    proposed_chart: String,
+    discussed_chart: String,
+    context: Context,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AttendanceContext {
+    stats: Vec<AttendanceStats>,
+    context: Context,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorContext {
+    message: String,
     context: Context,
 }
 
@@ -52,48 +189,182 @@ pub struct MailContext {
     context: Context,
 }
 
+// templates can only look up maps with string keys
+fn by_id<T>(map: HashMap<i32, T>) -> HashMap<String, T> {
+    map.into_iter().map(|(id, v)| (id.to_string(), v)).collect()
+}
+
+fn today() -> NaiveDate {
+    Utc::now().naive_utc().into()
+}
+
+// tags of the items, dropping all items without `tag` if one is given
+async fn filter_tagged(
+    items: &mut Vec<Item>,
+    tag: Option<&str>,
+    conn: &DbConn,
+) -> HashMap<i32, Vec<String>> {
+    let tags = Tag::for_items(items.iter().map(|item| item.id).collect(), conn).await;
+    if let Some(tag) = tag {
+        items.retain(|item| tags[&item.id].iter().any(|t| t == tag));
+    }
+    tags
+}
+
 impl Context {
     fn new(flash: Option<(String, String)>) -> Context {
         Context {
             flash,
+            user_id: None,
             username: None,
-            is_admin: None
+            is_admin: None,
+            unread: 0,
         }
     }
 
     fn for_user(user: &User, flash: Option<(String, String)>) -> Context {
         Context {
             flash,
+            user_id: Some(user.id),
             username: Some(user.username.clone()),
-            is_admin: Some(user.is_admin)
+            is_admin: Some(user.is_admin),
+            unread: 0,
         }
     }
 }
 
 impl VoteContext {
-    pub async fn new(conn: &DbConn, flash: Option<(String, String)>) -> VoteContext {
+    pub async fn new(
+        tracks: &TrackConfig,
+        endorsement: &EndorsementConfig,
+        fairness: &FairnessConfig,
+        conn: &DbConn,
+        flash: Option<(String, String)>,
+    ) -> VoteContext {
+        let election = Vote::run_election(None, fairness, endorsement, conn).await;
+        let next = Item::get_decided(conn).await;
+        let next_presenter = match &next {
+            Some(item) => Presenter::for_item(item.id, conn).await,
+            None => None,
+        };
         VoteContext {
-            next: Item::get_decided(conn).await,
-            winner: Vote::run_election(conn).await,
+            next,
+            next_presenter,
+            checked_in: false,
+            today: today(),
+            winner: election.winner,
+            transcript: election.transcript,
             second: None,
+            tracks: Vote::run_track_elections(tracks, fairness, endorsement, conn).await,
             items: Vec::new(),
+            recommendations: Vec::new(),
+            personalized: false,
+            unendorsed: Vec::new(),
+            endorsements: HashMap::new(),
+            endorsed: Vec::new(),
+            endorsements_to_rank: 0,
+            volunteers: HashMap::new(),
+            comments: HashMap::new(),
+            attachments: HashMap::new(),
+            tags: HashMap::new(),
+            all_tags: Vec::new(),
+            tag: None,
             context: Context::new(flash),
         }
     }
 
+    /// Items without `tag` stay on the ballot so their rank is kept, the template hides them.
+    /// Ranked items without enough endorsements stay hidden on the ballot for the same reason.
     pub async fn for_user(
         user: &User,
+        tag: Option<String>,
+        tracks: &TrackConfig,
+        endorsement: &EndorsementConfig,
+        fairness: &FairnessConfig,
         conn: &DbConn,
         flash: Option<(String, String)>,
     ) -> VoteContext {
-        let winner = Vote::run_election(conn).await;
-        let second = Vote::run_second_election(conn, winner.clone()).await;
+        let election = Vote::run_election(None, fairness, endorsement, conn).await;
+        let second =
+            Vote::run_second_election(conn, fairness, endorsement, election.winner.clone()).await;
+        let endorsements = Endorsement::counts(conn).await;
+        let all_items = Item::get_user_and_votes(user.id, conn).await;
+        let is_ranked =
+            |item: &Item| endorsement.is_ranked(endorsements.get(&item.id).copied().unwrap_or(0));
+        let ids: Vec<i32> = all_items.iter().map(|(item, _)| item.id).collect();
+        let unendorsed: Vec<Item> = all_items
+            .iter()
+            .filter(|(item, _)| !is_ranked(item))
+            .map(|(item, _)| item.clone())
+            .collect();
+        let items: Vec<_> = all_items
+            .into_iter()
+            .filter(|(item, rank)| rank.is_some() || is_ranked(item))
+            .collect();
+        let next = Item::get_decided(conn).await;
+        let next_presenter = match &next {
+            Some(item) => Presenter::for_item(item.id, conn).await,
+            None => None,
+        };
+        let checked_in = match &next {
+            Some(item) => Attendance::for_items(vec![item.id], conn)
+                .await
+                .remove(&item.id)
+                .is_some_and(|attendees| attendees.iter().any(|a| a.user_id == user.id)),
+            None => false,
+        };
+        let tags = Tag::for_items(ids.clone(), conn).await;
+        let candidates = items
+            .iter()
+            .filter(|(item, rank)| {
+                rank.is_none()
+                    && tag
+                        .as_ref()
+                        .is_none_or(|t| tags.get(&item.id).is_some_and(|ts| ts.contains(t)))
+            })
+            .map(|(item, _)| item.id)
+            .collect();
+        let Recommendations {
+            personalized,
+            items: recommended,
+        } = Recommendation::for_user(user.id, candidates, conn).await;
+        let recommendations = recommended
+            .into_iter()
+            .filter_map(|r| {
+                let item = items.iter().find(|(item, _)| item.id == r.item_id)?;
+                Some((item.0.clone(), r))
+            })
+            .collect();
+        let volunteer_ids = ids
+            .iter()
+            .copied()
+            .chain(next.iter().map(|item| item.id))
+            .collect();
+        let mut context = Context::for_user(user, flash);
+        context.unread = Notification::unread_count(user.id, conn).await;
         VoteContext {
-            next: Item::get_decided(conn).await,
-            winner,
+            next,
+            next_presenter,
+            checked_in,
+            today: today(),
+            winner: election.winner,
+            transcript: election.transcript,
             second,
-            items: Item::get_user_and_votes(user.id, conn).await,
-            context: Context::for_user(user, flash),
+            tracks: Vote::run_track_elections(tracks, fairness, endorsement, conn).await,
+            items,
+            recommendations,
+            personalized,
+            unendorsed,
+            endorsements: by_id(endorsements),
+            endorsed: Endorsement::of_user(user.id, conn).await,
+            endorsements_to_rank: endorsement.endorsements_to_rank,
+            volunteers: by_id(Volunteer::for_items(volunteer_ids, conn).await),
+            comments: by_id(Comment::for_items(ids.clone(), conn).await),
+            attachments: by_id(Attachment::for_items(ids.clone(), conn).await),
+            tags: by_id(tags),
+            all_tags: Tag::all(conn).await,
+            tag,
+            context,
         }
     }
 }
@@ -115,21 +386,118 @@ impl UserContext {
 impl ItemContext {
     pub async fn for_user_history(
         user: &User,
+        tag: Option<String>,
         conn: &DbConn,
         flash: Option<(String, String)>,
     ) -> ItemContext {
+        let mut items = Item::get_history(conn).await;
+        let tags = filter_tagged(&mut items, tag.as_deref(), conn).await;
+        let members = if user.is_admin {
+            User::from_ids(Vec::new(), conn)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|u| u.is_approved)
+                .map(|u| (u.id, u.username))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let ids: Vec<i32> = items.iter().map(|item| item.id).collect();
         ItemContext {
-            items: Item::get_history(conn).await,
+            items,
+            transitions: HashMap::new(),
+            comments: by_id(Comment::for_items(ids.clone(), conn).await),
+            attachments: by_id(Attachment::for_items(ids.clone(), conn).await),
+            notes: by_id(MeetingNotes::for_items(ids.clone(), conn).await),
+            ratings: by_id(RatingSummary::for_items(ids.clone(), conn).await),
+            my_ratings: by_id(RatingSummary::of_user(user.id, conn).await),
+            presenters: by_id(Presenter::for_items(ids.clone(), conn).await),
+            volunteers: HashMap::new(),
+            attendance: by_id(Attendance::for_items(ids, conn).await),
+            members,
+            today: today(),
+            tags: by_id(tags),
+            all_tags: Tag::all(conn).await,
+            tag,
+            context: Context::for_user(user, flash),
+        }
+    }
+    pub async fn for_user_pending(
+        user: &User,
+        conn: &DbConn,
+        flash: Option<(String, String)>,
+    ) -> ItemContext {
+        ItemContext {
+            items: Item::get_pending(conn).await,
+            transitions: HashMap::new(),
+            comments: HashMap::new(),
+            attachments: HashMap::new(),
+            notes: HashMap::new(),
+            ratings: HashMap::new(),
+            my_ratings: HashMap::new(),
+            presenters: HashMap::new(),
+            volunteers: HashMap::new(),
+            attendance: HashMap::new(),
+            members: Vec::new(),
+            today: today(),
+            tags: HashMap::new(),
+            all_tags: Vec::new(),
+            tag: None,
             context: Context::for_user(user, flash),
         }
     }
     pub async fn for_user_full(
         user: &User,
+        tag: Option<String>,
         conn: &DbConn,
         flash: Option<(String, String)>,
     ) -> ItemContext {
+        let mut items = Item::get_all(conn).await;
+        let tags = filter_tagged(&mut items, tag.as_deref(), conn).await;
+        let ids: Vec<i32> = items.iter().map(|item| item.id).collect();
         ItemContext {
-            items: Item::get_all(conn).await,
+            items,
+            transitions: ItemStatus::ALL
+                .iter()
+                .map(|s| (*s, s.transitions()))
+                .collect(),
+            comments: HashMap::new(),
+            attachments: HashMap::new(),
+            notes: HashMap::new(),
+            ratings: HashMap::new(),
+            my_ratings: HashMap::new(),
+            presenters: by_id(Presenter::for_items(ids.clone(), conn).await),
+            volunteers: by_id(Volunteer::for_items(ids, conn).await),
+            attendance: HashMap::new(),
+            members: Vec::new(),
+            today: today(),
+            tags: by_id(tags),
+            all_tags: Tag::all(conn).await,
+            tag,
+            context: Context::for_user(user, flash),
+        }
+    }
+}
+
+impl DraftContext {
+    pub fn for_user(draft: NewItemData, duplicates: Vec<Duplicate>, user: &User) -> DraftContext {
+        DraftContext {
+            draft,
+            duplicates,
+            context: Context::for_user(user, None),
+        }
+    }
+}
+
+impl ImportContext {
+    pub fn for_user(
+        entries: Vec<ImportPreview>,
+        user: &User,
+        flash: Option<(String, String)>,
+    ) -> ImportContext {
+        ImportContext {
+            entries,
             context: Context::for_user(user, flash),
         }
     }
@@ -137,18 +505,155 @@ impl ItemContext {
 
 impl EditContext {
     pub async fn for_user(
-        id: i32,
+        item: Item,
         user: &User,
         conn: &DbConn,
         flash: Option<(String, String)>,
     ) -> EditContext {
         EditContext {
-            item: Item::from_id(id, conn).await,
+            status_changes: StatusChange::for_item(item.id, conn).await,
+            tags: Tag::for_item(item.id, conn).await,
+            item: Some(item),
+            context: Context::for_user(user, flash),
+        }
+    }
+}
+
+impl RevisionContext {
+    /// Compares revision `to` (default: the latest) with `from` (default: its predecessor).
+    pub async fn for_user(
+        item: Item,
+        from: Option<i32>,
+        to: Option<i32>,
+        user: &User,
+        conn: &DbConn,
+        flash: Option<(String, String)>,
+    ) -> RevisionContext {
+        let revisions = Revision::for_item(item.id, conn).await;
+        let to_pos = match to {
+            Some(id) => revisions.iter().position(|r| r.id == id),
+            None => revisions.len().checked_sub(1),
+        };
+        let from_pos = match from {
+            Some(id) => revisions.iter().position(|r| r.id == id),
+            None => to_pos.and_then(|pos| pos.checked_sub(1)),
+        };
+
+        let to = to_pos.map(|pos| revisions[pos].clone());
+        let from = from_pos.map(|pos| revisions[pos].clone());
+        let diff = match (&from, &to) {
+            (Some(from), Some(to)) => from.diff(to),
+            _ => Vec::new(),
+        };
+        RevisionContext {
+            item,
+            revisions,
+            from,
+            to,
+            diff,
+            context: Context::for_user(user, flash),
+        }
+    }
+}
+
+impl SearchContext {
+    pub fn for_user(
+        query: SearchQuery,
+        hits: Vec<SearchHit>,
+        user: &User,
+        flash: Option<(String, String)>,
+    ) -> SearchContext {
+        SearchContext {
+            query,
+            statuses: &ItemStatus::ALL,
+            hits,
+            context: Context::for_user(user, flash),
+        }
+    }
+}
+
+impl NotificationContext {
+    pub async fn for_user(
+        user: &User,
+        conn: &DbConn,
+        flash: Option<(String, String)>,
+    ) -> NotificationContext {
+        let notifications = Notification::for_user(user.id, conn).await;
+        let mut context = Context::for_user(user, flash);
+        context.unread = notifications.iter().filter(|n| !n.is_read).count() as i64;
+        let ids: Vec<i32> = notifications.iter().filter_map(|n| n.item_id).collect();
+        // no ids would select all items
+        let items = if ids.is_empty() {
+            HashMap::new()
+        } else {
+            Item::from_ids(ids, Default::default(), conn)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .map(|item| (item.id, item))
+                .collect()
+        };
+        NotificationContext {
+            notifications,
+            items: by_id(items),
+            context,
+        }
+    }
+}
+
+impl PresenterContext {
+    pub fn for_user(
+        stats: Vec<PresenterStats>,
+        user: &User,
+        flash: Option<(String, String)>,
+    ) -> PresenterContext {
+        PresenterContext {
+            stats,
+            context: Context::for_user(user, flash),
+        }
+    }
+}
+
+impl StatsContext {
+    pub fn for_user(
+        stats: ActivityStats,
+        user: &User,
+        flash: Option<(String, String)>,
+    ) -> StatsContext {
+        // label every fourth week and every second month
+        StatsContext {
+            ballots_chart: chart::bar_chart(&stats.ballots_per_week, 4),
+            voters_chart: chart::bar_chart(&stats.voters_per_week, 4),
+            proposed_chart: chart::bar_chart(&stats.proposed_per_month, 2),
+            discussed_chart: chart::bar_chart(&stats.discussed_per_month, 2),
+            stats,
+            context: Context::for_user(user, flash),
+        }
+    }
+}
+
+impl AttendanceContext {
+    pub fn for_user(
+        stats: Vec<AttendanceStats>,
+        user: &User,
+        flash: Option<(String, String)>,
+    ) -> AttendanceContext {
+        AttendanceContext {
+            stats,
             context: Context::for_user(user, flash),
         }
     }
 }
 
+impl ErrorContext {
+    pub fn for_user(user: &User, message: String) -> ErrorContext {
+        ErrorContext {
+            message,
+            context: Context::for_user(user, None),
+        }
+    }
+}
+
 impl MailContext {
     pub async fn new(
         id: i32,
@@ -2,25 +2,74 @@
 extern crate rocket;
 
 mod context;
-mod markdown;
 
+use chrono::NaiveDate;
 use rocket::figment::value::magic::RelativePathBuf;
 use rocket::form::Form;
 use rocket::fs::FileServer;
-use rocket::http::{Cookie, CookieJar, Status};
+use rocket::http::{ContentType, Cookie, CookieJar, Header, Status};
 use rocket::request::FlashMessage;
+use rocket::response::status::Custom;
 use rocket::response::{Flash, Redirect};
 use rocket::serde::json::Json;
+use rocket::tokio::fs::File;
+use rocket::tokio::io::AsyncReadExt;
 use rocket::{fairing::AdHoc, Request, State};
 use rocket_dyn_templates::Template;
+use std::time::Duration;
 
-use context::{EditContext, Empty, ItemContext, MailContext, UserContext, VoteContext};
-use markdown::markdown_to_html;
-use prank::item::{ChangeItemData, Item, MailItemData, NewItemData};
+use context::{
+    AttendanceContext, DraftContext, EditContext, Empty, ErrorContext, ImportContext,
+    ImportPreview, ItemContext, MailContext, NotificationContext, PresenterContext,
+    RevisionContext, SearchContext, StatsContext, UserContext, VoteContext,
+};
+use prank::attachment::{Attachment, AttachmentConfig, DeleteAttachmentData, UploadAttachmentData};
+use prank::attendance::{Attendance, AttendanceData, AttendanceStats, Attendee};
+use prank::chart;
+use prank::comment::{ChangeCommentData, Comment, DeleteCommentData, NewCommentData};
+use prank::duplicate::{self, Duplicate};
+use prank::endorsement::{EndorseData, Endorsement, EndorsementConfig};
+use prank::expiry::{ExpiryConfig, ReviveData};
+use prank::export::{self, Format as ExportFormat};
+use prank::fairness::FairnessConfig;
+use prank::import::{self, ImportData, UploadData};
+use prank::item::{
+    ChangeItemData, ChangeStatusData, Forbidden, Item, ItemFilter, MailItemData, MergeItemsData,
+    NewItemData, PreviewData,
+};
+use prank::limits::SubmissionLimits;
+use prank::lookup::{self, LookupConfig, Prefill};
+use prank::markdown::markdown_to_html;
+use prank::meeting::{MeetingNotes, NotesData, RatingData, RatingSummary};
+use prank::moderation::{ModerateData, ModerationConfig};
+use prank::notification::Notification;
+use prank::presenter::{AssignPresenterData, Presenter, PresenterStats, Volunteer, VolunteerData};
+use prank::recommendation::{Recommendation, Recommendations};
+use prank::revision::{DiffLine, Revision, RollbackData};
+use prank::search::{SearchHit, SearchQuery};
+use prank::stats::ActivityStats;
+use prank::status::{ItemStatus, StatusChange};
+use prank::tag::{self, Tag};
+use prank::track::{TrackConfig, TrackWinner};
 use prank::user::{AdminUser, NewPassword, NewUser, User};
 use prank::vote::{Ballot, Vote};
 use prank::{DbConn, MailConfig};
 
+fn forbidden(user: &User, message: String) -> Custom<Template> {
+    Custom(
+        Status::Forbidden,
+        Template::render("403", ErrorContext::for_user(user, message)),
+    )
+}
+
+// pages showing comment threads
+fn back_to(back: &str) -> Redirect {
+    match back {
+        "history" => Redirect::to(uri!(history(tag = _))),
+        _ => Redirect::to(uri!(index)),
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 // Post Routes
 ///////////////////////////////////////////////////////////////////////////////
@@ -41,7 +90,7 @@ async fn login(
         match u {
             Ok(x) => {
                 jar.add_private(Cookie::new("user_id", x.id.to_string()));
-                Ok(Redirect::to(uri!(index_user)))
+                Ok(Redirect::to(uri!(index_user(tag = _))))
             }
             Err(e) => Err(Flash::error(Redirect::to(uri!(user)), e.to_string())),
         }
@@ -74,7 +123,7 @@ async fn register(input: Form<NewUser>, conn: DbConn) -> Flash<Redirect> {
 #[post("/change_password", data = "<input>")]
 async fn change_password(input: Form<NewPassword>, user: &User, conn: DbConn) -> Flash<Redirect> {
     let new_password = input.into_inner();
-    let change = User::change_password(&user, new_password, &conn).await;
+    let change = User::change_password(user, new_password, &conn).await;
     match change {
         Ok(_) => Flash::success(Redirect::to(uri!(user)), "Sucessfully changed password"),
         Err(e) => Flash::error(Redirect::to(uri!(user)), e.to_string()),
@@ -90,38 +139,430 @@ async fn vote(ballot: Json<Ballot>, user: &User, conn: DbConn) -> Status {
     }
 }
 
-#[post("/preview", data = "<markdown>")]
-async fn preview(markdown: &str, _user: &User, _conn: DbConn) -> Option<String> {
-    markdown_to_html(markdown).ok()
+#[post("/endorse", data = "<data>")]
+async fn endorse(data: Form<EndorseData>, user: &User, conn: DbConn) -> Flash<Redirect> {
+    let res = Endorsement::set(data.item, user.id, data.endorse, &conn).await;
+    match res {
+        Ok(_) if data.endorse => Flash::success(Redirect::to(uri!(index)), "Endorsed item"),
+        Ok(_) => Flash::success(Redirect::to(uri!(index)), "Withdrew endorsement"),
+        Err(e) => Flash::error(Redirect::to(uri!(index)), e.to_string()),
+    }
+}
+
+#[post("/volunteer", data = "<data>")]
+async fn volunteer(data: Form<VolunteerData>, user: &User, conn: DbConn) -> Flash<Redirect> {
+    let res = Volunteer::set(data.item, user.id, data.volunteer, &conn).await;
+    match res {
+        Ok(_) if data.volunteer => {
+            Flash::success(Redirect::to(uri!(index)), "Volunteered to present the item")
+        }
+        Ok(_) => Flash::success(Redirect::to(uri!(index)), "Withdrew offer to present"),
+        Err(e) => Flash::error(Redirect::to(uri!(index)), e.to_string()),
+    }
+}
+
+#[post("/preview", data = "<preview>")]
+async fn preview(
+    preview: Form<PreviewData>,
+    _user: &User,
+    _conn: DbConn,
+) -> Result<String, Custom<String>> {
+    let PreviewData { markdown, bib } = preview.into_inner();
+    bib.normalized()
+        .and_then(|bib| bib.render(&markdown))
+        .map_err(|e| Custom(Status::BadRequest, e.to_string()))
 }
 
 #[post("/new_item", data = "<item>")]
-async fn add_new_item(item: Form<NewItemData>, _user: &User, conn: DbConn) -> Flash<Redirect> {
+async fn add_new_item(
+    item: Form<NewItemData>,
+    user: &User,
+    moderation: &State<ModerationConfig>,
+    limits: &State<SubmissionLimits>,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Template> {
     let mut item_data = item.into_inner();
-    item_data.html = match markdown_to_html(&item_data.markdown) {
-        Ok(html) => html,
-        Err(e) => return Flash::error(Redirect::to(uri!(add_new_item)), e.to_string()),
-    };
+    if let Err(e) = item_data.render() {
+        return Ok(Flash::error(
+            Redirect::to(uri!(add_new_item)),
+            e.to_string(),
+        ));
+    }
+
+    // let the proposer confirm the submission if it looks like a known paper
+    if !item_data.confirm {
+        let card = item_data.bib.compose(&item_data.markdown);
+        let duplicates = duplicate::find(&item_data.title, &card, &conn).await;
+        if !duplicates.is_empty() {
+            return Err(Template::render(
+                "item",
+                DraftContext::for_user(item_data, duplicates, user),
+            ));
+        }
+    }
 
-    let res = Item::add(item_data, &conn).await;
+    let status = moderation.initial_status(user);
+    let res = Item::add(item_data, Some(user.id), status, limits, &conn).await;
     match res {
-        Ok(_) => Flash::success(Redirect::to(uri!(index)), "Added item to db"),
-        Err(e) => Flash::error(Redirect::to(uri!(add_new_item)), e.to_string()),
+        Ok(_) if status == ItemStatus::Pending => Ok(Flash::success(
+            Redirect::to(uri!(index)),
+            "Added item, it will be on the ballot once an admin approved it",
+        )),
+        Ok(_) => Ok(Flash::success(
+            Redirect::to(uri!(index)),
+            "Added item to db",
+        )),
+        Err(e) => Ok(Flash::error(
+            Redirect::to(uri!(add_new_item)),
+            e.to_string(),
+        )),
+    }
+}
+
+#[post("/import", data = "<upload>")]
+async fn upload_import(
+    upload: Form<UploadData<'_>>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Template, Flash<Redirect>> {
+    let upload = upload.into_inner();
+    let mut content = String::new();
+    let read = match upload.file.open().await {
+        Ok(mut file) => file.read_to_string(&mut content).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = read {
+        return Err(Flash::error(Redirect::to(uri!(import_form)), e.to_string()));
+    }
+
+    let entries = import::parse(&content, upload.format)
+        .map_err(|e| Flash::error(Redirect::to(uri!(import_form)), e.to_string()))?;
+    let items = Item::get_all(&conn).await;
+    let mut previews = Vec::new();
+    for entry in entries {
+        let item = entry
+            .to_item()
+            .map_err(|e| Flash::error(Redirect::to(uri!(import_form)), e.to_string()))?;
+        let duplicates = duplicate::find_in(&item.title, &item.bib.compose(&item.markdown), &items);
+        previews.push(ImportPreview { item, duplicates });
+    }
+    Ok(Template::render(
+        "import",
+        ImportContext::for_user(previews, user, None),
+    ))
+}
+
+#[post("/import_confirm", data = "<import>")]
+async fn confirm_import(
+    import: Form<ImportData>,
+    user: &User,
+    moderation: &State<ModerationConfig>,
+    limits: &State<SubmissionLimits>,
+    conn: DbConn,
+) -> Flash<Redirect> {
+    let entries = import.into_inner().entries;
+    let status = moderation.initial_status(user);
+    let mut added = 0;
+    for entry in entries
+        .into_iter()
+        .filter(|e| e.add && !e.title.trim().is_empty())
+    {
+        let mut item_data = NewItemData {
+            title: entry.title,
+            html: String::new(),
+            markdown: entry.markdown,
+            bib: entry.bib,
+            tags: String::new(),
+            confirm: true,
+        };
+        if let Err(e) = item_data.render() {
+            return Flash::error(Redirect::to(uri!(import_form)), e.to_string());
+        }
+        if let Err(e) = Item::add(item_data, Some(user.id), status, limits, &conn).await {
+            return Flash::error(
+                Redirect::to(uri!(import_form)),
+                format!("Imported {} items before failing: {}", added, e),
+            );
+        }
+        added += 1;
     }
+    Flash::success(
+        Redirect::to(uri!(index)),
+        format!("Imported {} items", added),
+    )
 }
 
 #[post("/update_item", data = "<item>")]
-async fn update_item(item: Form<ChangeItemData>, _user: &User, conn: DbConn) -> Flash<Redirect> {
+async fn update_item(
+    item: Form<ChangeItemData>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
     let mut item_data = item.into_inner();
-    item_data.html = match markdown_to_html(&item_data.markdown) {
+    let id = item_data.id;
+    if let Err(e) = item_data.render() {
+        return Ok(Flash::error(
+            Redirect::to(uri!(edit_id(id = id))),
+            e.to_string(),
+        ));
+    }
+
+    let res = Item::update(item_data, user, &conn).await;
+    match res {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to(uri!(index)),
+            "Changed item in db",
+        )),
+        Err(e) if e.is::<Forbidden>() => Err(forbidden(user, e.to_string())),
+        Err(e) => Ok(Flash::error(
+            Redirect::to(uri!(edit_id(id = id))),
+            e.to_string(),
+        )),
+    }
+}
+
+#[post("/comment", data = "<comment>")]
+async fn add_comment(comment: Form<NewCommentData>, user: &User, conn: DbConn) -> Flash<Redirect> {
+    let comment = comment.into_inner();
+    let html = match markdown_to_html(&comment.markdown) {
+        Ok(html) => html,
+        Err(e) => return Flash::error(back_to(&comment.back), e.to_string()),
+    };
+
+    let res = Comment::add(comment.item, user.id, comment.markdown, html, &conn).await;
+    match res {
+        Ok(_) => Flash::success(back_to(&comment.back), "Added comment"),
+        Err(e) => Flash::error(back_to(&comment.back), e.to_string()),
+    }
+}
+
+#[post("/edit_comment", data = "<comment>")]
+async fn edit_comment(
+    comment: Form<ChangeCommentData>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
+    let comment = comment.into_inner();
+    let html = match markdown_to_html(&comment.markdown) {
         Ok(html) => html,
-        Err(e) => return Flash::error(Redirect::to(uri!(add_new_item)), e.to_string()),
+        Err(e) => return Ok(Flash::error(back_to(&comment.back), e.to_string())),
     };
 
-    let res = Item::update(item_data, &conn).await;
+    let res = Comment::update(comment.id, user, comment.markdown, html, &conn).await;
+    match res {
+        Ok(_) => Ok(Flash::success(back_to(&comment.back), "Changed comment")),
+        Err(e) if e.is::<Forbidden>() => Err(forbidden(user, e.to_string())),
+        Err(e) => Ok(Flash::error(back_to(&comment.back), e.to_string())),
+    }
+}
+
+#[post("/delete_comment", data = "<comment>")]
+async fn delete_comment(
+    comment: Form<DeleteCommentData>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
+    let comment = comment.into_inner();
+    let res = Comment::delete(comment.id, user, &conn).await;
+    match res {
+        Ok(_) => Ok(Flash::success(back_to(&comment.back), "Deleted comment")),
+        Err(e) if e.is::<Forbidden>() => Err(forbidden(user, e.to_string())),
+        Err(e) => Ok(Flash::error(back_to(&comment.back), e.to_string())),
+    }
+}
+
+#[post("/attach", data = "<upload>")]
+async fn attach_files(
+    upload: Form<UploadAttachmentData<'_>>,
+    user: &User,
+    config: &State<AttachmentConfig>,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
+    let mut upload = upload.into_inner();
+    let mut added = 0;
+    // an empty file input is sent as an empty file
+    for file in upload.files.iter_mut().filter(|f| f.len() > 0) {
+        match Attachment::add(upload.item, file, user, config, &conn).await {
+            Ok(_) => added += 1,
+            Err(e) if e.is::<Forbidden>() => return Err(forbidden(user, e.to_string())),
+            Err(e) => return Ok(Flash::error(back_to(&upload.back), format!("{:#}", e))),
+        }
+    }
+    Ok(Flash::success(
+        back_to(&upload.back),
+        format!("Attached {} files", added),
+    ))
+}
+
+#[post("/delete_attachment", data = "<attachment>")]
+async fn delete_attachment(
+    attachment: Form<DeleteAttachmentData>,
+    user: &User,
+    config: &State<AttachmentConfig>,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
+    let res = Attachment::delete(attachment.id, user, config, &conn).await;
+    match res {
+        Ok(_) => Ok(Flash::success(
+            back_to(&attachment.back),
+            "Deleted attachment",
+        )),
+        Err(e) if e.is::<Forbidden>() => Err(forbidden(user, e.to_string())),
+        Err(e) => Ok(Flash::error(back_to(&attachment.back), e.to_string())),
+    }
+}
+
+#[post("/attendance", data = "<data>")]
+async fn check_in(
+    data: Form<AttendanceData>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
+    let uid = data.user.unwrap_or(user.id);
+    let res = Attendance::set(data.item, uid, data.present, user, &conn).await;
+    match res {
+        Ok(_) if data.present => Ok(Flash::success(back_to(&data.back), "Checked in")),
+        Ok(_) => Ok(Flash::success(back_to(&data.back), "Removed check-in")),
+        Err(e) if e.is::<Forbidden>() => Err(forbidden(user, e.to_string())),
+        Err(e) => Ok(Flash::error(back_to(&data.back), e.to_string())),
+    }
+}
+
+#[post("/notes", data = "<notes>")]
+async fn save_notes(
+    notes: Form<NotesData>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
+    let notes = notes.into_inner();
+    let html = match markdown_to_html(&notes.minutes) {
+        Ok(html) => html,
+        Err(e) => {
+            return Ok(Flash::error(
+                Redirect::to(uri!(history(tag = _))),
+                e.to_string(),
+            ))
+        }
+    };
+
+    let res = MeetingNotes::save(notes.item, notes.minutes, html, notes.slides, user, &conn).await;
+    match res {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to(uri!(history(tag = _))),
+            "Saved notes",
+        )),
+        Err(e) if e.is::<Forbidden>() => Err(forbidden(user, e.to_string())),
+        Err(e) => Ok(Flash::error(
+            Redirect::to(uri!(history(tag = _))),
+            e.to_string(),
+        )),
+    }
+}
+
+#[post("/rate", data = "<rating>")]
+async fn rate(
+    rating: Form<RatingData>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
+    let res = RatingSummary::rate(rating.item, user.id, rating.rating, &conn).await;
+    match res {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to(uri!(history(tag = _))),
+            "Saved rating",
+        )),
+        Err(e) if e.is::<Forbidden>() => Err(forbidden(user, e.to_string())),
+        Err(e) => Ok(Flash::error(
+            Redirect::to(uri!(history(tag = _))),
+            e.to_string(),
+        )),
+    }
+}
+
+#[post("/set_status", data = "<data>")]
+async fn set_status(
+    data: Form<ChangeStatusData>,
+    _admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Flash<Redirect> {
+    let data = data.into_inner();
+    let res = Item::set_status(data.id, data.status, &conn).await;
+    match res {
+        Ok(_) => Flash::success(Redirect::to(uri!(edit(tag = _))), "Changed item status"),
+        Err(e) => Flash::error(Redirect::to(uri!(edit(tag = _))), e.to_string()),
+    }
+}
+
+#[post("/assign_presenter", data = "<data>")]
+async fn assign_presenter(
+    data: Form<AssignPresenterData>,
+    _admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Flash<Redirect> {
+    let res = Presenter::assign(data.item, data.user, &conn).await;
+    match res {
+        Ok(_) => Flash::success(Redirect::to(uri!(edit(tag = _))), "Changed presenter"),
+        Err(e) => Flash::error(Redirect::to(uri!(edit(tag = _))), e.to_string()),
+    }
+}
+
+#[post("/moderate", data = "<data>")]
+async fn moderate(
+    data: Form<ModerateData>,
+    _admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Flash<Redirect> {
+    let data = data.into_inner();
+    let res = Item::moderate(data.id, data.approve, data.reason, &conn).await;
+    match res {
+        Ok(_) if data.approve => Flash::success(Redirect::to(uri!(moderation)), "Approved item"),
+        Ok(_) => Flash::success(Redirect::to(uri!(moderation)), "Rejected item"),
+        Err(e) => Flash::error(Redirect::to(uri!(moderation)), e.to_string()),
+    }
+}
+
+#[post("/revive", data = "<data>")]
+async fn revive(
+    data: Form<ReviveData>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Flash<Redirect>, Custom<Template>> {
+    let res = Item::revive(data.id, user, &conn).await;
+    match res {
+        Ok(_) => Ok(Flash::success(
+            Redirect::to(uri!(notifications)),
+            "Revived item",
+        )),
+        Err(e) if e.is::<Forbidden>() => Err(forbidden(user, e.to_string())),
+        Err(e) => Ok(Flash::error(
+            Redirect::to(uri!(notifications)),
+            e.to_string(),
+        )),
+    }
+}
+
+#[post("/merge", data = "<data>")]
+async fn merge_items(
+    data: Form<MergeItemsData>,
+    _admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Flash<Redirect> {
+    let res = Item::merge(data.keep, data.drop, &conn).await;
+    match res {
+        Ok(_) => Flash::success(Redirect::to(uri!(edit(tag = _))), "Merged items"),
+        Err(e) => Flash::error(Redirect::to(uri!(edit(tag = _))), e.to_string()),
+    }
+}
+
+#[post("/rollback", data = "<data>")]
+async fn rollback(data: Form<RollbackData>, admin: AdminUser<'_>, conn: DbConn) -> Flash<Redirect> {
+    let data = data.into_inner();
+    let id = data.id;
+    let res = Item::rollback(id, data.revision, admin.user.id, &conn).await;
+    let to = uri!(revisions(id = id, from = _, to = _));
     match res {
-        Ok(_) => Flash::success(Redirect::to(uri!(index)), "Changed item in db"),
-        Err(e) => Flash::error(Redirect::to(uri!(add_new_item)), e.to_string()),
+        Ok(_) => Flash::success(Redirect::to(to), "Rolled back item"),
+        Err(e) => Flash::error(Redirect::to(to), e.to_string()),
     }
 }
 
@@ -143,27 +584,192 @@ async fn mail_item(
 ///////////////////////////////////////////////////////////////////////////////
 // GET Routes
 ///////////////////////////////////////////////////////////////////////////////
-#[get("/history")]
-async fn history(flash: Option<FlashMessage<'_>>, user: &User, conn: DbConn) -> Template {
+// an empty tag selects all items
+fn tag_filter(tag: Option<&str>) -> Option<String> {
+    tag.map(tag::normalize).filter(|t| !t.is_empty())
+}
+
+#[get("/history?<tag>")]
+async fn history(
+    tag: Option<&str>,
+    flash: Option<FlashMessage<'_>>,
+    user: &User,
+    conn: DbConn,
+) -> Template {
     let flash = flash.map(FlashMessage::into_inner);
     Template::render(
         "history",
-        ItemContext::for_user_history(user, &conn, flash).await,
+        ItemContext::for_user_history(user, tag_filter(tag), &conn, flash).await,
     )
 }
 
+#[get("/ratings.csv")]
+async fn ratings_csv(_user: &User, conn: DbConn) -> (ContentType, String) {
+    let items = Item::get_history(&conn).await;
+    let ids = items.iter().map(|item| item.id).collect();
+    let summaries = RatingSummary::for_items(ids, &conn).await;
+    (ContentType::CSV, RatingSummary::to_csv(&items, &summaries))
+}
+
+#[derive(Responder)]
+struct Download {
+    inner: (ContentType, String),
+    disposition: Header<'static>,
+}
+
+// empty date inputs are sent as empty strings
+fn parse_date(date: Option<&str>) -> Result<Option<NaiveDate>, Status> {
+    match date {
+        None | Some("") => Ok(None),
+        Some(d) => NaiveDate::parse_from_str(d, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| Status::BadRequest),
+    }
+}
+
+#[derive(Responder)]
+struct AttachmentFile {
+    inner: (ContentType, File),
+    disposition: Header<'static>,
+}
+
+#[get("/attachment?<id>")]
+async fn attachment(
+    id: i32,
+    _user: &User,
+    config: &State<AttachmentConfig>,
+    conn: DbConn,
+) -> Option<AttachmentFile> {
+    let attachment = Attachment::from_id(id, &conn).await?;
+    let file = File::open(attachment.path(config)).await.ok()?;
+    let content_type =
+        ContentType::parse_flexible(&attachment.content_type).unwrap_or(ContentType::Binary);
+    Some(AttachmentFile {
+        inner: (content_type, file),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("inline; filename=\"{}\"", attachment.filename),
+        ),
+    })
+}
+
+#[get("/history/export?<format>&<from>&<to>")]
+async fn history_export(
+    format: ExportFormat,
+    from: Option<&str>,
+    to: Option<&str>,
+    _user: &User,
+    conn: DbConn,
+) -> Result<Download, Status> {
+    let filter = ItemFilter {
+        discussed_only: true,
+        statuses: vec![
+            ItemStatus::Scheduled,
+            ItemStatus::Discussed,
+            ItemStatus::FollowUp,
+        ],
+        discussed_from: parse_date(from)?,
+        discussed_to: parse_date(to)?,
+        ..Default::default()
+    };
+    let mut items = Item::from_ids(Vec::new(), filter, &conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    items.sort_by_key(|item| item.discussed_on);
+    let content = export::export(&items, format).map_err(|_| Status::InternalServerError)?;
+    Ok(Download {
+        inner: (format.content_type(), content),
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"history.{}\"", format.extension()),
+        ),
+    })
+}
+
+const SEARCH_LIMIT: usize = 50;
+
+async fn search_items(
+    query: &SearchQuery,
+    user: &User,
+    conn: &DbConn,
+) -> Result<Vec<SearchHit>, Status> {
+    let filter = ItemFilter {
+        statuses: query.status.clone(),
+        // items waiting for moderation are only visible to admins
+        exclude_pending: !user.is_admin,
+        discussed_from: parse_date(query.from.as_deref())?,
+        discussed_to: parse_date(query.to.as_deref())?,
+        ..Default::default()
+    };
+    Item::search(&query.q, filter, query.limit.unwrap_or(SEARCH_LIMIT), conn)
+        .await
+        .map_err(|_| Status::InternalServerError)
+}
+
+#[get("/search?<query..>")]
+async fn search(
+    query: SearchQuery,
+    flash: Option<FlashMessage<'_>>,
+    user: &User,
+    conn: DbConn,
+) -> Result<Template, Status> {
+    let flash = flash.map(FlashMessage::into_inner);
+    let hits = search_items(&query, user, &conn).await?;
+    Ok(Template::render(
+        "search",
+        SearchContext::for_user(query, hits, user, flash),
+    ))
+}
+
+#[get("/search.json?<query..>")]
+async fn search_json(
+    query: SearchQuery,
+    user: &User,
+    conn: DbConn,
+) -> Result<Json<Vec<SearchHit>>, Status> {
+    search_items(&query, user, &conn).await.map(Json)
+}
+
 #[get("/edit?<id>")]
 async fn edit_id(
     id: i32,
     flash: Option<FlashMessage<'_>>,
-    admin: AdminUser<'_>,
+    user: &User,
     conn: DbConn,
-) -> Template {
+) -> Option<Result<Template, Custom<Template>>> {
     let flash = flash.map(FlashMessage::into_inner);
-    Template::render(
+    let item = Item::from_id(id, &conn).await?;
+    if !item.can_edit(user) {
+        return Some(Err(forbidden(
+            user,
+            "You are not allowed to edit this item.".to_string(),
+        )));
+    }
+    Some(Ok(Template::render(
         "item",
-        EditContext::for_user(id, admin.user, &conn, flash).await,
-    )
+        EditContext::for_user(item, user, &conn, flash).await,
+    )))
+}
+
+#[get("/revisions?<id>&<from>&<to>")]
+async fn revisions(
+    id: i32,
+    from: Option<i32>,
+    to: Option<i32>,
+    flash: Option<FlashMessage<'_>>,
+    user: &User,
+    conn: DbConn,
+) -> Option<Template> {
+    let flash = flash.map(FlashMessage::into_inner);
+    let item = Item::from_id(id, &conn).await?;
+    // pending items stay hidden like in the item list, only their proposer may see them
+    if item.status == ItemStatus::Pending && !item.can_edit(user) {
+        return None;
+    }
+    Some(Template::render(
+        "revisions",
+        RevisionContext::for_user(item, from, to, user, &conn, flash).await,
+    ))
 }
 
 #[get("/mail?<id>")]
@@ -181,12 +787,89 @@ async fn mail_form(
     )
 }
 
-#[get("/show")]
-async fn edit(flash: Option<FlashMessage<'_>>, admin: AdminUser<'_>, conn: DbConn) -> Template {
+#[get("/show?<tag>")]
+async fn edit(
+    tag: Option<&str>,
+    flash: Option<FlashMessage<'_>>,
+    admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Template {
     let flash = flash.map(FlashMessage::into_inner);
     Template::render(
         "show",
-        ItemContext::for_user_full(admin.user, &conn, flash).await,
+        ItemContext::for_user_full(admin.user, tag_filter(tag), &conn, flash).await,
+    )
+}
+
+#[get("/presenters")]
+async fn presenters(
+    flash: Option<FlashMessage<'_>>,
+    admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Result<Template, Status> {
+    let flash = flash.map(FlashMessage::into_inner);
+    let stats = Presenter::report(&conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(Template::render(
+        "presenters",
+        PresenterContext::for_user(stats, admin.user, flash),
+    ))
+}
+
+#[get("/stats")]
+async fn stats(
+    flash: Option<FlashMessage<'_>>,
+    admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Result<Template, Status> {
+    let flash = flash.map(FlashMessage::into_inner);
+    let stats = ActivityStats::compute(&conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(Template::render(
+        "stats",
+        StatsContext::for_user(stats, admin.user, flash),
+    ))
+}
+
+#[get("/stats.json")]
+async fn stats_json(_admin: AdminUser<'_>, conn: DbConn) -> Result<Json<ActivityStats>, Status> {
+    ActivityStats::compute(&conn)
+        .await
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+#[get("/attendance")]
+async fn attendance(
+    flash: Option<FlashMessage<'_>>,
+    admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Result<Template, Status> {
+    let flash = flash.map(FlashMessage::into_inner);
+    let mut stats: Vec<AttendanceStats> = Attendance::stats(&conn)
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .into_values()
+        .collect();
+    stats.sort_by(|a, b| a.username.cmp(&b.username));
+    Ok(Template::render(
+        "attendance",
+        AttendanceContext::for_user(stats, admin.user, flash),
+    ))
+}
+
+#[get("/moderation")]
+async fn moderation(
+    flash: Option<FlashMessage<'_>>,
+    admin: AdminUser<'_>,
+    conn: DbConn,
+) -> Template {
+    let flash = flash.map(FlashMessage::into_inner);
+    Template::render(
+        "moderation",
+        ItemContext::for_user_pending(admin.user, &conn, flash).await,
     )
 }
 
@@ -202,22 +885,73 @@ async fn user(flash: Option<FlashMessage<'_>>, conn: DbConn) -> Template {
     Template::render("login", UserContext::new(&conn, flash).await)
 }
 
+/// Shows and marks all notifications of the user as read.
+#[get("/notifications")]
+async fn notifications(flash: Option<FlashMessage<'_>>, user: &User, conn: DbConn) -> Template {
+    let flash = flash.map(FlashMessage::into_inner);
+    let context = NotificationContext::for_user(user, &conn, flash).await;
+    let _ = Notification::mark_read(user.id, &conn).await;
+    Template::render("notifications", context)
+}
+
 #[get("/new_item")]
 async fn new_item(flash: Option<FlashMessage<'_>>, user: &User, _conn: DbConn) -> Template {
     let flash = flash.map(FlashMessage::into_inner);
     Template::render("item", UserContext::for_user(user, flash).await)
 }
 
-#[get("/")]
-async fn index_user(flash: Option<FlashMessage<'_>>, user: &User, conn: DbConn) -> Template {
+/// Prefills the item editor from the metadata of a DOI or an arXiv ID.
+#[get("/lookup?<doi>&<arxiv>")]
+async fn lookup_item(
+    doi: Option<&str>,
+    arxiv: Option<&str>,
+    _user: &User,
+    config: &State<LookupConfig>,
+) -> Result<Json<Prefill>, Custom<String>> {
+    lookup::find(config, doi, arxiv)
+        .await
+        .and_then(Prefill::from_entry)
+        .map(Json)
+        .map_err(|e| Custom(Status::BadGateway, e.to_string()))
+}
+
+#[get("/import")]
+async fn import_form(flash: Option<FlashMessage<'_>>, user: &User, _conn: DbConn) -> Template {
     let flash = flash.map(FlashMessage::into_inner);
-    Template::render("vote", VoteContext::for_user(user, &conn, flash).await)
+    Template::render("import", ImportContext::for_user(Vec::new(), user, flash))
+}
+
+#[get("/?<tag>")]
+async fn index_user(
+    tag: Option<&str>,
+    flash: Option<FlashMessage<'_>>,
+    user: &User,
+    tracks: &State<TrackConfig>,
+    endorsement: &State<EndorsementConfig>,
+    fairness: &State<FairnessConfig>,
+    conn: DbConn,
+) -> Template {
+    let flash = flash.map(FlashMessage::into_inner);
+    let tag = tag_filter(tag);
+    Template::render(
+        "vote",
+        VoteContext::for_user(user, tag, tracks, endorsement, fairness, &conn, flash).await,
+    )
 }
 
 #[get("/", rank = 2)]
-async fn index(flash: Option<FlashMessage<'_>>, conn: DbConn) -> Template {
+async fn index(
+    flash: Option<FlashMessage<'_>>,
+    tracks: &State<TrackConfig>,
+    endorsement: &State<EndorsementConfig>,
+    fairness: &State<FairnessConfig>,
+    conn: DbConn,
+) -> Template {
     let flash = flash.map(FlashMessage::into_inner);
-    Template::render("index", VoteContext::new(&conn, flash).await)
+    Template::render(
+        "index",
+        VoteContext::new(tracks, endorsement, fairness, &conn, flash).await,
+    )
 }
 
 #[catch(404)]
@@ -225,18 +959,69 @@ fn not_found(_req: &Request) -> Template {
     Template::render("404", Empty::new())
 }
 
+// how often stale items are expired while the server is running
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 #[launch]
 fn rocket() -> _ {
     let rocket = rocket::build()
         .attach(DbConn::fairing())
         .attach(Template::fairing())
         .attach(AdHoc::config::<MailConfig>())
+        .attach(AdHoc::config::<LookupConfig>())
+        .attach(AdHoc::config::<AttachmentConfig>())
+        .attach(AdHoc::config::<ModerationConfig>())
+        .attach(AdHoc::config::<SubmissionLimits>())
+        .attach(AdHoc::config::<TrackConfig>())
+        .attach(AdHoc::config::<EndorsementConfig>())
+        .attach(AdHoc::config::<FairnessConfig>())
+        .attach(AdHoc::config::<ExpiryConfig>())
+        .attach(AdHoc::on_liftoff("Item expiry", |rocket| {
+            Box::pin(async move {
+                let Some(config) = rocket.state::<ExpiryConfig>().cloned() else {
+                    return;
+                };
+                let Some(conn) = DbConn::get_one(rocket).await else {
+                    return;
+                };
+                rocket::tokio::spawn(async move {
+                    loop {
+                        if let Err(e) = Item::expire_stale(&config, &conn).await {
+                            error!("Failed to expire stale items: {}", e);
+                        }
+                        rocket::tokio::time::sleep(EXPIRY_INTERVAL).await;
+                    }
+                });
+            })
+        }))
         .register("/", catchers![not_found])
         .mount(
             // get routes
             "/",
             routes![
-                index, index_user, new_item, user, user_user, history, edit, edit_id, mail_form
+                index,
+                index_user,
+                new_item,
+                import_form,
+                lookup_item,
+                user,
+                user_user,
+                notifications,
+                history,
+                ratings_csv,
+                history_export,
+                attachment,
+                search,
+                search_json,
+                edit,
+                presenters,
+                attendance,
+                stats,
+                stats_json,
+                moderation,
+                edit_id,
+                revisions,
+                mail_form
             ],
         )
         .mount(
@@ -248,9 +1033,27 @@ fn rocket() -> _ {
                 register,
                 change_password,
                 vote,
+                endorse,
+                volunteer,
                 preview,
                 add_new_item,
+                upload_import,
+                confirm_import,
                 update_item,
+                add_comment,
+                edit_comment,
+                delete_comment,
+                attach_files,
+                delete_attachment,
+                check_in,
+                save_notes,
+                rate,
+                set_status,
+                assign_presenter,
+                moderate,
+                revive,
+                merge_items,
+                rollback,
                 mail_item
             ],
         );
@@ -2,10 +2,24 @@ use structopt::StructOpt;
 
 use anyhow::{Context, Error, Result};
 use chrono::NaiveDate;
-use prank::item::{Item, ItemFormat};
+use prank::attachment::AttachmentConfig;
+use prank::attendance::Attendance;
+use prank::duplicate;
+use prank::expiry::ExpiryConfig;
+use prank::export::{self, Format as ExportFormat};
+use prank::import::{self, Entry, Format};
+use prank::item::{Item, ItemFilter, ItemFormat, NewItemData};
+use prank::limits::SubmissionLimits;
+use prank::lookup::{self, LookupConfig};
+use prank::meeting::RatingSummary;
+use prank::presenter::Presenter;
+use prank::status::{ItemStatus, StatusChange};
+use prank::tag::Tag;
 use prank::user::User;
 use prank::DbConn;
 use rocket::fairing::Fairing;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
 
 use prank::mail;
 
@@ -24,16 +38,38 @@ enum UsersSubcommand {
     Reject(IdOptions),
     List(IdOptions),
     Delete(IdsOnly),
-    GeneratePassword { id: i32 },
+    GeneratePassword {
+        id: i32,
+    },
+    /// how often every approved user presented, who presented least first
+    Presenters,
 }
 
 #[derive(StructOpt, Debug)]
 enum ItemsSubcommand {
-    List {
-        #[structopt(flatten)]
-        id_opt: IdOptions,
+    List(ItemFilterOptions),
+    /// print the selected items as BibTeX, CSV or JSON
+    Export {
         #[structopt(flatten)]
-        date_opt: ItemDateOption,
+        filter_opt: ItemFilterOptions,
+        /// bibtex, csv or json
+        #[structopt(long, default_value = "bibtex")]
+        format: ExportFormat,
+    },
+    /// full-text search in the title, notes, authors and venue of all items, best matches first
+    Search {
+        query: Vec<String>,
+        /// only items in this status, can be given multiple times
+        #[structopt(long)]
+        status: Vec<ItemStatus>,
+        /// only items discussed on or after this date
+        #[structopt(long)]
+        from: Option<NaiveDate>,
+        /// only items discussed on or before this date
+        #[structopt(long)]
+        to: Option<NaiveDate>,
+        #[structopt(long, default_value = "20")]
+        limit: usize,
     },
     Delete(IdsOnly),
     DiscussOn {
@@ -43,8 +79,71 @@ enum ItemsSubcommand {
     CancelDiscuss {
         id: i32,
     },
+    SetStatus {
+        id: i32,
+        status: ItemStatus,
+    },
+    /// merge item `drop` into item `keep`, keeping the better rank of every ballot
+    Merge {
+        keep: i32,
+        drop: i32,
+    },
+    StatusLog {
+        id: i32,
+    },
     Dump(ItemDumpCommand),
     Mail(MailCommand),
+    /// add an item from the metadata of a DOI or an arXiv ID, or by entering it manually
+    Add(AddCommand),
+    /// add items from a BibTeX, RIS or CSL-JSON file, asking for every entry
+    Import(ImportCommand),
+    /// print the aggregated ratings of all discussed items as CSV
+    Ratings,
+    /// expire stale open items according to the policy in Rocket.toml and notify their proposers
+    Expire {
+        /// only print the items which would expire
+        #[structopt(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+struct ItemFilterOptions {
+    #[structopt(flatten)]
+    id_opt: IdOptions,
+    #[structopt(flatten)]
+    date_opt: ItemDateOption,
+    /// only items in this status, can be given multiple times
+    #[structopt(long)]
+    status: Vec<ItemStatus>,
+    /// only items proposed by the user with this id
+    #[structopt(long)]
+    proposer: Option<i32>,
+    /// only items discussed on or after this date
+    #[structopt(long)]
+    from: Option<NaiveDate>,
+    /// only items discussed on or before this date
+    #[structopt(long)]
+    to: Option<NaiveDate>,
+    /// only items with this tag
+    #[structopt(long)]
+    tag: Option<String>,
+}
+
+impl ItemFilterOptions {
+    async fn items(self, conn: &DbConn) -> Result<Vec<Item>> {
+        let filter = ItemFilter {
+            discussed_only: self.date_opt.discussed,
+            undiscussed_only: self.date_opt.undiscussed,
+            statuses: self.status,
+            exclude_pending: false,
+            proposed_by: self.proposer,
+            discussed_from: self.from,
+            discussed_to: self.to,
+            tag: self.tag,
+        };
+        Item::from_ids(self.id_opt.ids, filter, conn).await
+    }
 }
 
 #[derive(StructOpt, Debug)]
@@ -62,6 +161,34 @@ struct ItemDumpCommand {
     id: i32,
 }
 
+#[derive(StructOpt, Debug)]
+struct AddCommand {
+    #[structopt(long)]
+    doi: Option<String>,
+    #[structopt(long)]
+    arxiv: Option<String>,
+    /// id of the user the item is proposed by
+    #[structopt(long)]
+    proposer: Option<i32>,
+    /// add the item without asking
+    #[structopt(short = "y", long)]
+    yes: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct ImportCommand {
+    file: PathBuf,
+    /// bibtex, ris or csl-json, detected from the content by default
+    #[structopt(long)]
+    format: Option<Format>,
+    /// id of the user the items are proposed by
+    #[structopt(long)]
+    proposer: Option<i32>,
+    /// import all entries without asking
+    #[structopt(short = "y", long)]
+    yes: bool,
+}
+
 #[derive(StructOpt, Debug)]
 struct MailCommand {
     id: i32,
@@ -93,7 +220,7 @@ struct IdsOnly {
 
 async fn handle_users_command(cmd: UsersSubcommand, conn: &DbConn) -> Result<()> {
     use UsersSubcommand::*;
-    return match cmd {
+    match cmd {
         Admin(o) => {
             let rows = User::set_admin(o.ids, true, conn).await?;
             println!("Made {} admins", rows);
@@ -122,7 +249,19 @@ async fn handle_users_command(cmd: UsersSubcommand, conn: &DbConn) -> Result<()>
         List(o) => {
             let users = User::from_ids(o.ids, conn).await?;
             println!("Found {} users", users.len());
-            users.iter().for_each(|u| println!("{:?}", u));
+            let attendance = Attendance::stats(conn).await?;
+            for u in &users {
+                println!("{:?}", u);
+                if let Some(s) = attendance.get(&u.id) {
+                    let last = s
+                        .last_attended
+                        .map_or("never".to_string(), |d| d.to_string());
+                    println!(
+                        "    attended {} of {} meetings (last: {})",
+                        s.attended, s.meetings, last
+                    );
+                }
+            }
             Ok(())
         }
         GeneratePassword { id } => {
@@ -130,26 +269,73 @@ async fn handle_users_command(cmd: UsersSubcommand, conn: &DbConn) -> Result<()>
             println!("Set random password {} for id {}", pass, id);
             Ok(())
         }
-    };
+        Presenters => {
+            let stats = Presenter::report(conn).await?;
+            println!("user,presented,upcoming,last_presented,volunteered");
+            stats.iter().for_each(|s| {
+                println!(
+                    "{},{},{},{},{}",
+                    s.username,
+                    s.presented,
+                    s.upcoming,
+                    s.last_presented.map(|d| d.to_string()).unwrap_or_default(),
+                    s.volunteered
+                );
+            });
+            Ok(())
+        }
+    }
 }
 
-async fn handle_items_command(cmd: ItemsSubcommand, conn: &DbConn) -> Result<()> {
+async fn handle_items_command(cmd: ItemsSubcommand, conn: &DbConn, config: &Config) -> Result<()> {
     use ItemsSubcommand::*;
-    return match cmd {
-        List { id_opt, date_opt } => {
-            let items =
-                Item::from_ids(id_opt.ids, date_opt.discussed, date_opt.undiscussed, conn).await?;
+    match cmd {
+        List(o) => {
+            let items = o.items(conn).await?;
+            let tags = Tag::for_items(items.iter().map(|item| item.id).collect(), conn).await;
             println!("Found {} items", items.len());
             items.iter().for_each(|u| {
                 println!(
-                    "Item {{ id: {}, title: {}, markdown: <omitted>, discussed_on: {:?} }}",
-                    u.id, u.title, u.discussed_on
+                    "Item {{ id: {}, title: {}, markdown: <omitted>, discussed_on: {:?}, status: {}, proposed_by: {:?}, created_at: {}, tags: {:?} }}",
+                    u.id, u.title, u.discussed_on, u.status, u.proposer, u.created_at, tags[&u.id]
+                );
+            });
+            Ok(())
+        }
+        Export { filter_opt, format } => {
+            let items = filter_opt.items(conn).await?;
+            print!("{}", export::export(&items, format)?);
+            Ok(())
+        }
+        Search {
+            query,
+            status,
+            from,
+            to,
+            limit,
+        } => {
+            let filter = ItemFilter {
+                statuses: status,
+                discussed_from: from,
+                discussed_to: to,
+                ..Default::default()
+            };
+            let hits = Item::search(&query.join(" "), filter, limit, conn).await?;
+            println!("Found {} items", hits.len());
+            hits.iter().for_each(|hit| {
+                let item = &hit.item;
+                println!(
+                    "{:>5}  {:<10} {:<10}  {}",
+                    item.id,
+                    item.status.as_str(),
+                    item.discussed_on.map(|d| d.to_string()).unwrap_or_default(),
+                    item.title
                 );
             });
             Ok(())
         }
         Delete(o) => {
-            let rows = Item::delete(o.ids, conn).await?;
+            let rows = Item::delete(o.ids, &config.attachment, conn).await?;
             println!("Deleted {} items", rows);
             Ok(())
         }
@@ -163,6 +349,24 @@ async fn handle_items_command(cmd: ItemsSubcommand, conn: &DbConn) -> Result<()>
             println!("Updated item {}", id);
             Ok(())
         }
+        SetStatus { id, status } => {
+            Item::set_status(id, status, conn).await?;
+            println!("Updated item {}", id);
+            Ok(())
+        }
+        Merge { keep, drop } => {
+            Item::merge(keep, drop, conn).await?;
+            println!("Merged item {} into item {}", drop, keep);
+            Ok(())
+        }
+        StatusLog { id } => {
+            let changes = StatusChange::for_item(id, conn).await;
+            println!("Found {} status changes", changes.len());
+            changes.iter().for_each(|s| {
+                println!("{}: {} -> {}", s.changed_at, s.old_status, s.new_status);
+            });
+            Ok(())
+        }
         Dump(o) => {
             let item = Item::from_id(o.id, conn)
                 .await
@@ -185,20 +389,191 @@ async fn handle_items_command(cmd: ItemsSubcommand, conn: &DbConn) -> Result<()>
                 .ok_or(Error::msg("Item not found"))?;
             let password = rpassword::prompt_password_stdout("Password: ")
                 .context("Error getting password")?;
+            let presenter = Presenter::for_item(item.id, conn).await;
             mail::send(
-                &item, o.from, o.to, o.comment, o.username, o.server, password,
+                &item, presenter, o.from, o.to, o.comment, o.username, o.server, password,
             )?;
             println!("Send mail was successful");
             Ok(())
         }
+        Add(o) => add_item(o, conn, &config.lookup).await,
+        Import(o) => import_items(o, conn).await,
+        Ratings => {
+            let items = Item::get_history(conn).await;
+            let ids = items.iter().map(|item| item.id).collect();
+            let summaries = RatingSummary::for_items(ids, conn).await;
+            print!("{}", RatingSummary::to_csv(&items, &summaries));
+            Ok(())
+        }
+        Expire { dry_run } => {
+            let items = if dry_run {
+                Item::stale(&config.expiry, conn).await?
+            } else {
+                Item::expire_stale(&config.expiry, conn).await?
+            };
+            println!(
+                "{} {} items",
+                if dry_run { "Would expire" } else { "Expired" },
+                items.len()
+            );
+            items
+                .iter()
+                .for_each(|(item, reason)| println!("{}: {} ({})", item.id, item.title, reason));
+            Ok(())
+        }
+    }
+}
+
+// y(es), n(o) or q(uit)
+fn confirm(question: &str) -> Result<Option<bool>> {
+    loop {
+        print!("{} [y/n/q] ", question);
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Some(true)),
+            "n" | "no" => return Ok(Some(false)),
+            "q" | "quit" => return Ok(None),
+            _ => (),
+        }
+    }
+}
+
+fn print_draft(item_data: &NewItemData, items: &[Item]) {
+    let card = item_data.bib.compose(&item_data.markdown);
+    println!("{}\n\n{}", item_data.title, card);
+    for d in duplicate::find_in(&item_data.title, &card, items) {
+        println!(
+            "Possible duplicate of item {} ({}, {}): {}",
+            d.id,
+            d.title,
+            d.status,
+            d.reasons.join(", ")
+        );
+    }
+}
+
+fn ask(question: &str) -> Result<String> {
+    print!("{}: ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+// fallback if the paper could not be looked up
+fn ask_entry(doi: Option<String>, arxiv: Option<String>) -> Result<Entry> {
+    let title = ask("Title")?;
+    if title.is_empty() {
+        return Err(Error::msg("Title must not be empty"));
+    }
+    let authors = ask("Authors (comma separated)")?;
+    let optional = |answer: String| (!answer.is_empty()).then_some(answer);
+    Ok(Entry {
+        title,
+        authors: authors
+            .split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .map(String::from)
+            .collect(),
+        year: optional(ask("Year")?),
+        venue: optional(ask("Venue")?),
+        summary: optional(ask("Topic")?),
+        doi,
+        arxiv,
+        url: optional(ask("Link")?),
+    })
+}
+
+async fn add_item(o: AddCommand, conn: &DbConn, lookup_config: &LookupConfig) -> Result<()> {
+    let entry = match lookup::find(lookup_config, o.doi.as_deref(), o.arxiv.as_deref()).await {
+        Ok(entry) => entry,
+        Err(e) => {
+            println!("{}\nPlease enter the paper manually", e);
+            ask_entry(o.doi, o.arxiv)?
+        }
     };
+    let item_data = entry.to_item()?;
+    print_draft(&item_data, &Item::get_all(conn).await);
+
+    if !o.yes && confirm("Add this item?")? != Some(true) {
+        return Ok(());
+    }
+    // added by the operator, so neither moderation nor limits apply
+    Item::add(
+        item_data,
+        o.proposer,
+        ItemStatus::Open,
+        &SubmissionLimits::default(),
+        conn,
+    )
+    .await?;
+    println!("Added item");
+    Ok(())
 }
 
-async fn handle_command(args: PrankCtl, conn: &DbConn) -> Result<()> {
-    return match args {
+async fn import_items(o: ImportCommand, conn: &DbConn) -> Result<()> {
+    let content = std::fs::read_to_string(&o.file)
+        .with_context(|| format!("Could not read {}", o.file.display()))?;
+    let format = o.format.or_else(|| {
+        o.file
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| ext.parse().ok())
+    });
+    let entries = import::parse(&content, format)?;
+    println!("Found {} entries", entries.len());
+
+    let mut items = Item::get_all(conn).await;
+    let mut added = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        println!("##############################");
+        if entry.title.is_empty() {
+            println!("Skipping entry {} without title", i + 1);
+            continue;
+        }
+        let item_data = entry.to_item()?;
+        print_draft(&item_data, &items);
+
+        if !o.yes {
+            match confirm("Import this entry?")? {
+                Some(true) => (),
+                Some(false) => continue,
+                None => break,
+            }
+        }
+        Item::add(
+            item_data,
+            o.proposer,
+            ItemStatus::Open,
+            &SubmissionLimits::default(),
+            conn,
+        )
+        .await?;
+        added += 1;
+        // entries later in the file are also checked against the ones just added
+        items = Item::get_all(conn).await;
+    }
+    println!("Imported {} items", added);
+    Ok(())
+}
+
+/// Settings of the server in `Rocket.toml` which are also used by commands.
+struct Config {
+    lookup: LookupConfig,
+    attachment: AttachmentConfig,
+    expiry: ExpiryConfig,
+}
+
+async fn handle_command(args: PrankCtl, conn: &DbConn, config: &Config) -> Result<()> {
+    match args {
         PrankCtl::Users(c) => handle_users_command(c, conn).await,
-        PrankCtl::Items(c) => handle_items_command(c, conn).await,
-    };
+        PrankCtl::Items(c) => handle_items_command(c, conn, config).await,
+    }
 }
 
 #[rocket::main]
@@ -211,7 +586,19 @@ async fn main() {
     let conn = DbConn::get_one(&rocket)
         .await
         .expect("Unable to establish db connection.");
-    if let Err(e) = handle_command(args, &conn).await {
+    let figment = rocket.figment();
+    let config = Config {
+        lookup: figment
+            .extract()
+            .expect("Invalid lookup configuration in Rocket.toml."),
+        attachment: figment
+            .extract()
+            .expect("Invalid attachment configuration in Rocket.toml."),
+        expiry: figment
+            .extract()
+            .expect("Invalid expiry configuration in Rocket.toml."),
+    };
+    if let Err(e) = handle_command(args, &conn, &config).await {
         println!("Error: {}", e);
     }
 }
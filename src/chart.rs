@@ -0,0 +1,62 @@
+use std::fmt::Write;
+
+use crate::markdown::escape_html;
+use crate::stats::Period;
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 200;
+// space for the axis labels
+const LEFT: usize = 30;
+const RIGHT: usize = 40;
+const BOTTOM: usize = 20;
+const TOP: usize = 10;
+
+/// Renders the periods as an SVG bar chart, every bar shows its label and count on hover.
+///
+/// Only every `label_every`-th period is labeled below the bars to keep the labels readable.
+pub fn bar_chart(periods: &[Period], label_every: usize) -> String {
+    let max = periods.iter().map(|p| p.count).max().unwrap_or(0).max(1);
+    let plot_height = (HEIGHT - TOP - BOTTOM) as f64;
+    let slot = (WIDTH - LEFT - RIGHT) as f64 / periods.len().max(1) as f64;
+    let bar = (slot * 0.8).max(1.0);
+
+    let mut svg = String::new();
+    let _ = write!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {w} {h}" width="100%" role="img" font-size="10" font-family="sans-serif">"#,
+        w = WIDTH,
+        h = HEIGHT
+    );
+    // y axis with the maximum, bars are scaled to it
+    let _ = write!(
+        svg,
+        r##"<line x1="{x}" y1="{top}" x2="{x}" y2="{bottom}" stroke="#6c757d"/><line x1="{x}" y1="{bottom}" x2="{w}" y2="{bottom}" stroke="#6c757d"/><text x="{tx}" y="{ty}" text-anchor="end">{max}</text><text x="{tx}" y="{bottom}" text-anchor="end">0</text>"##,
+        x = LEFT,
+        top = TOP,
+        bottom = HEIGHT - BOTTOM,
+        w = WIDTH - RIGHT,
+        tx = LEFT - 4,
+        ty = TOP + 8,
+        max = max
+    );
+    for (i, period) in periods.iter().enumerate() {
+        let height = period.count as f64 / max as f64 * plot_height;
+        let x = LEFT as f64 + i as f64 * slot + (slot - bar) / 2.0;
+        let label = escape_html(&period.label);
+        let _ = write!(
+            svg,
+            r##"<rect x="{x:.1}" y="{y:.1}" width="{bar:.1}" height="{height:.1}" fill="#007bff"><title>{label}: {count}</title></rect>"##,
+            y = (HEIGHT - BOTTOM) as f64 - height,
+            count = period.count
+        );
+        if label_every > 0 && i % label_every == 0 {
+            let _ = write!(
+                svg,
+                r#"<text x="{x:.1}" y="{y}">{label}</text>"#,
+                y = HEIGHT - 6
+            );
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
@@ -0,0 +1,290 @@
+use super::*;
+
+use anyhow::Error;
+use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::sql;
+use diesel::sql_types::Integer;
+use item::{Forbidden, Item};
+use rocket::data::ByteUnit;
+use rocket::fs::TempFile;
+use rocket::http::ContentType;
+use rocket::tokio::io::AsyncReadExt;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use user::User;
+
+/// Storage of uploaded files, configured in `Rocket.toml`.
+///
+/// The `file` and `data-form` limits of Rocket have to be at least `attachment_max_size`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct AttachmentConfig {
+    /// files are stored under their id in this directory
+    pub attachment_dir: PathBuf,
+    pub attachment_max_size: ByteUnit,
+    /// accepted MIME types, e.g. "application/pdf"
+    pub attachment_types: Vec<String>,
+}
+
+impl Default for AttachmentConfig {
+    fn default() -> AttachmentConfig {
+        AttachmentConfig {
+            attachment_dir: PathBuf::from("attachments"),
+            attachment_max_size: ByteUnit::Mebibyte(10),
+            attachment_types: vec!["application/pdf".to_string()],
+        }
+    }
+}
+
+#[derive(Serialize, Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = self::schema::attachments)]
+#[serde(crate = "rocket::serde")]
+pub struct Attachment {
+    pub id: i32,
+    pub item_id: i32,
+    pub filename: String,
+    pub content_type: String,
+    /// in bytes
+    pub size: i64,
+    pub uploaded_by: Option<i32>,
+    pub uploaded_at: NaiveDateTime,
+    /// username of the uploader, taken from the joined users table
+    #[diesel(select_expression = self::schema::users::username.nullable())]
+    #[diesel(select_expression_type = diesel::dsl::Nullable<self::schema::users::username>)]
+    pub uploader: Option<String>,
+}
+
+#[derive(FromForm)]
+pub struct UploadAttachmentData<'r> {
+    pub item: i32,
+    pub files: Vec<TempFile<'r>>,
+    pub back: String,
+}
+
+#[derive(FromForm)]
+pub struct DeleteAttachmentData {
+    pub id: i32,
+    pub back: String,
+}
+
+// only the name of the file without any directories or characters breaking the header
+fn sanitize_filename(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .collect();
+    match name.trim() {
+        "" => "attachment".to_string(),
+        name => name.to_string(),
+    }
+}
+
+impl AttachmentConfig {
+    fn path(&self, id: i32) -> PathBuf {
+        self.attachment_dir.join(id.to_string())
+    }
+
+    // browsers send PDFs also as "application/octet-stream", so the extension decides then
+    fn content_type(&self, file: &TempFile<'_>, filename: &str) -> Result<ContentType> {
+        let declared = file
+            .content_type()
+            .filter(|t| **t != ContentType::Binary)
+            .cloned();
+        let guessed = filename
+            .rsplit_once('.')
+            .and_then(|(_, ext)| ContentType::from_extension(ext));
+        let content_type = declared.or(guessed).unwrap_or(ContentType::Binary);
+        let mime = format!("{}/{}", content_type.top(), content_type.sub());
+        if !self
+            .attachment_types
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(&mime))
+        {
+            return Err(Error::msg(format!(
+                "Files of type {} are not allowed, only {}.",
+                mime,
+                self.attachment_types.join(", ")
+            )));
+        }
+        Ok(content_type)
+    }
+}
+
+impl Attachment {
+    /// Attachments of the given items grouped by item id, oldest first.
+    pub async fn for_items(ids: Vec<i32>, conn: &DbConn) -> HashMap<i32, Vec<Attachment>> {
+        use self::schema::attachments::dsl::{
+            attachments, item_id as attachment_item_id, uploaded_at,
+        };
+
+        conn.run(move |c| {
+            attachments
+                .left_join(all_users)
+                .filter(attachment_item_id.eq_any(ids))
+                .order(uploaded_at.asc())
+                .select(Attachment::as_select())
+                .load::<Attachment>(c)
+                .unwrap_or_default()
+                .into_iter()
+                .fold(HashMap::new(), |mut map, attachment| {
+                    map.entry(attachment.item_id)
+                        .or_insert_with(Vec::new)
+                        .push(attachment);
+                    map
+                })
+        })
+        .await
+    }
+
+    pub async fn from_id(id: i32, conn: &DbConn) -> Option<Attachment> {
+        use self::schema::attachments::dsl::{attachments, id as attachment_id};
+
+        conn.run(move |c| {
+            attachments
+                .left_join(all_users)
+                .filter(attachment_id.eq(id))
+                .select(Attachment::as_select())
+                .get_result::<Attachment>(c)
+                .ok()
+        })
+        .await
+    }
+
+    /// Admins and the proposer of an item can attach files to it.
+    pub fn can_attach(item: &Item, user: &User) -> bool {
+        user.is_admin || item.proposed_by == Some(user.id)
+    }
+
+    /// Stores an uploaded file in the attachment directory and records it for item `id`.
+    pub async fn add(
+        id: i32,
+        file: &mut TempFile<'_>,
+        user: &User,
+        config: &AttachmentConfig,
+        conn: &DbConn,
+    ) -> Result<()> {
+        use self::schema::attachments::dsl as attachments;
+
+        let item = Item::from_id(id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        if !Attachment::can_attach(&item, user) {
+            return Err(Forbidden("You are not allowed to attach files to this item.").into());
+        }
+
+        let filename = sanitize_filename(
+            file.raw_name()
+                .map(|n| n.dangerous_unsafe_unsanitized_raw().as_str())
+                .unwrap_or_default(),
+        );
+        if file.len() > config.attachment_max_size {
+            return Err(Error::msg(format!(
+                "{} is larger than {}.",
+                filename, config.attachment_max_size
+            )));
+        }
+        let content_type = config
+            .content_type(file, &filename)
+            .with_context(|| format!("Cannot attach {}", filename))?;
+        if content_type == ContentType::PDF {
+            let mut magic = [0; 5];
+            let read = match file.open().await {
+                Ok(mut f) => f.read_exact(&mut magic).await.map(|_| ()),
+                Err(e) => Err(e),
+            };
+            if read.is_err() || &magic != b"%PDF-" {
+                return Err(Error::msg(format!("{} is not a PDF file.", filename)));
+            }
+        }
+
+        rocket::tokio::fs::create_dir_all(&config.attachment_dir)
+            .await
+            .context("Failed to create the attachment directory.")?;
+        let size = file.len() as i64;
+        let uid = user.id;
+        let mime = content_type.to_string();
+        let attachment_id = conn
+            .run(move |c| {
+                c.transaction(|c| {
+                    diesel::insert_into(attachments::attachments)
+                        .values((
+                            attachments::item_id.eq(id),
+                            attachments::filename.eq(filename),
+                            attachments::content_type.eq(mime),
+                            attachments::size.eq(size),
+                            attachments::uploaded_by.eq(uid),
+                            attachments::uploaded_at.eq(Utc::now().naive_utc()),
+                        ))
+                        .execute(c)
+                        .context("Failed inserting attachment into db.")?;
+                    diesel::select(sql::<Integer>("last_insert_rowid()"))
+                        .get_result::<i32>(c)
+                        .context("Failed inserting attachment into db.")
+                })
+            })
+            .await?;
+
+        if let Err(e) = file.move_copy_to(config.path(attachment_id)).await {
+            Attachment::remove_rows(vec![attachment_id], conn).await?;
+            return Err(Error::new(e).context("Failed to store the attachment."));
+        }
+        Ok(())
+    }
+
+    /// Uploaders, admins and the proposer of the item can delete an attachment.
+    pub async fn delete(
+        id: i32,
+        user: &User,
+        config: &AttachmentConfig,
+        conn: &DbConn,
+    ) -> Result<()> {
+        let attachment = Attachment::from_id(id, conn)
+            .await
+            .ok_or(Error::msg("Attachment not found in db."))?;
+        let item = Item::from_id(attachment.item_id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        if attachment.uploaded_by != Some(user.id) && !Attachment::can_attach(&item, user) {
+            return Err(Forbidden("You are not allowed to delete this attachment.").into());
+        }
+
+        Attachment::remove_rows(vec![id], conn).await?;
+        Attachment::remove_files(&[id], config).await;
+        Ok(())
+    }
+
+    async fn remove_rows(ids: Vec<i32>, conn: &DbConn) -> Result<()> {
+        use self::schema::attachments::dsl::{attachments, id as attachment_id};
+
+        conn.run(move |c| {
+            diesel::delete(attachments.filter(attachment_id.eq_any(ids)))
+                .execute(c)
+                .context("Failed to delete attachment from db.")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Ids of the attachments of the given items.
+    pub(crate) fn of_items(ids: &[i32], c: &mut SqliteConnection) -> Result<Vec<i32>> {
+        use self::schema::attachments::dsl::{attachments, id as attachment_id, item_id};
+
+        attachments
+            .filter(item_id.eq_any(ids))
+            .select(attachment_id)
+            .load::<i32>(c)
+            .context("Failed to read attachments from db.")
+    }
+
+    // files of rows which are already gone, missing files are fine
+    pub(crate) async fn remove_files(ids: &[i32], config: &AttachmentConfig) {
+        for id in ids {
+            let _ = rocket::tokio::fs::remove_file(config.path(*id)).await;
+        }
+    }
+
+    pub fn path(&self, config: &AttachmentConfig) -> PathBuf {
+        config.path(self.id)
+    }
+}
@@ -0,0 +1,210 @@
+use super::*;
+
+use self::schema::attendance::dsl::{
+    attendance, checked_in_at, checked_in_by, item_id as attendance_item_id,
+    user_id as attendance_user_id,
+};
+use anyhow::Error;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use item::{Forbidden, Item};
+use status::ItemStatus;
+use std::collections::HashMap;
+use user::User;
+
+/// User who attended the meeting of an item.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Attendee {
+    pub user_id: i32,
+    pub username: String,
+    pub checked_in_at: NaiveDateTime,
+}
+
+#[derive(FromForm)]
+pub struct AttendanceData {
+    pub item: i32,
+    /// the logged-in user if not given, only admins can check in others
+    pub user: Option<i32>,
+    /// `false` removes the check-in
+    pub present: bool,
+    pub back: String,
+}
+
+/// Meetings attended by a user, see `Attendance::stats`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct AttendanceStats {
+    pub user_id: i32,
+    pub username: String,
+    pub attended: usize,
+    /// meetings which took place so far
+    pub meetings: usize,
+    pub last_attended: Option<NaiveDate>,
+    /// share of all meetings the user attended, between 0 and 1
+    pub rate: f64,
+}
+
+pub struct Attendance;
+
+impl Attendance {
+    /// Attendees of the given items by item id, in the order they checked in.
+    pub async fn for_items(ids: Vec<i32>, conn: &DbConn) -> HashMap<i32, Vec<Attendee>> {
+        conn.run(move |c| {
+            attendance
+                .inner_join(all_users)
+                .filter(attendance_item_id.eq_any(ids))
+                .order(checked_in_at.asc())
+                .select((
+                    attendance_item_id,
+                    attendance_user_id,
+                    user_username,
+                    checked_in_at,
+                ))
+                .load::<(i32, i32, String, NaiveDateTime)>(c)
+                .unwrap_or_default()
+                .into_iter()
+                .fold(HashMap::new(), |mut map, (item, uid, username, at)| {
+                    map.entry(item).or_insert_with(Vec::new).push(Attendee {
+                        user_id: uid,
+                        username,
+                        checked_in_at: at,
+                    });
+                    map
+                })
+        })
+        .await
+    }
+
+    /// Checks user `uid` in for the meeting of item `id` or removes the check-in.
+    ///
+    /// Users can check themselves in on the day of the meeting, admins can change the attendance
+    /// of every user for every meeting.
+    pub async fn set(id: i32, uid: i32, present: bool, user: &User, conn: &DbConn) -> Result<()> {
+        if uid != user.id && !user.is_admin {
+            return Err(Forbidden("Only admins can check in other users.").into());
+        }
+        let item = Item::from_id(id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        let Some(date) = item.discussed_on.filter(|_| item.status.has_date()) else {
+            return Err(Error::msg("The item is not scheduled for a meeting."));
+        };
+        let today: NaiveDate = Utc::now().naive_utc().into();
+        if !user.is_admin && date != today {
+            return Err(Error::msg(format!(
+                "Check-in is only possible on the day of the meeting ({}).",
+                date
+            )));
+        }
+
+        let by = user.id;
+        conn.run(move |c| {
+            if present {
+                diesel::insert_or_ignore_into(attendance)
+                    .values((
+                        attendance_item_id.eq(id),
+                        attendance_user_id.eq(uid),
+                        checked_in_at.eq(Utc::now().naive_utc()),
+                        checked_in_by.eq(by),
+                    ))
+                    .execute(c)
+                    .context("Failed to save attendance in db.")?;
+            } else {
+                diesel::delete(
+                    attendance
+                        .filter(attendance_item_id.eq(id))
+                        .filter(attendance_user_id.eq(uid)),
+                )
+                .execute(c)
+                .context("Failed to delete attendance from db.")?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Attendance of all approved users by user id.
+    ///
+    /// Every scheduled, discussed or follow-up item with a date up to today counts as a meeting.
+    pub async fn stats(conn: &DbConn) -> Result<HashMap<i32, AttendanceStats>> {
+        conn.run(move |c| {
+            let today: NaiveDate = Utc::now().naive_utc().into();
+            let meetings = all_items
+                .filter(item_status.eq_any([
+                    ItemStatus::Scheduled,
+                    ItemStatus::Discussed,
+                    ItemStatus::FollowUp,
+                ]))
+                .filter(item_discussed_on.le(today))
+                .count()
+                .get_result::<i64>(c)
+                .context("Failed to read items from db.")? as usize;
+
+            let mut stats: HashMap<i32, AttendanceStats> = all_users
+                .filter(user_approved.eq(true))
+                .select((user_id, user_username))
+                .load::<(i32, String)>(c)
+                .context("Failed to read users from db.")?
+                .into_iter()
+                .map(|(id, username)| {
+                    let stats = AttendanceStats {
+                        user_id: id,
+                        username,
+                        attended: 0,
+                        meetings,
+                        last_attended: None,
+                        rate: 0.0,
+                    };
+                    (id, stats)
+                })
+                .collect();
+
+            let attended = attendance
+                .inner_join(all_items)
+                .filter(item_discussed_on.le(today))
+                .select((attendance_user_id, item_discussed_on))
+                .load::<(i32, Option<NaiveDate>)>(c)
+                .context("Failed to read attendance from db.")?;
+            for (uid, date) in attended {
+                if let Some(s) = stats.get_mut(&uid) {
+                    s.attended += 1;
+                    s.last_attended = s.last_attended.max(date);
+                }
+            }
+            for s in stats.values_mut().filter(|s| s.meetings > 0) {
+                s.rate = s.attended as f64 / s.meetings as f64;
+            }
+            Ok(stats)
+        })
+        .await
+    }
+
+    /// Whether user `uid` checked in for the meeting of item `id`.
+    pub(crate) fn attended(id: i32, uid: i32, c: &mut SqliteConnection) -> Result<bool> {
+        let found = attendance
+            .filter(attendance_item_id.eq(id))
+            .filter(attendance_user_id.eq(uid))
+            .count()
+            .get_result::<i64>(c)
+            .context("Failed to read attendance from db.")?;
+        Ok(found > 0)
+    }
+
+    /// Moves the attendance of item `drop` to `keep`, used when merging items.
+    pub(crate) fn merge(keep: i32, drop: i32, c: &mut SqliteConnection) -> Result<()> {
+        let attended_keep = attendance
+            .filter(attendance_item_id.eq(keep))
+            .select(attendance_user_id)
+            .load::<i32>(c)
+            .context("Failed to move attendance.")?;
+        diesel::update(
+            attendance
+                .filter(attendance_item_id.eq(drop))
+                .filter(attendance_user_id.ne_all(attended_keep)),
+        )
+        .set(attendance_item_id.eq(keep))
+        .execute(c)
+        .context("Failed to move attendance.")?;
+        Ok(())
+    }
+}
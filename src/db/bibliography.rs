@@ -0,0 +1,152 @@
+use super::*;
+
+use crate::duplicate;
+use crate::markdown::{self, markdown_to_html};
+use anyhow::Error;
+
+/// Optional structured information about the paper behind an item.
+///
+/// The rendered card of an item is composed from these fields followed by its free-form notes.
+#[derive(
+    Serialize,
+    Queryable,
+    Selectable,
+    Insertable,
+    AsChangeset,
+    FromForm,
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+)]
+#[diesel(table_name = self::schema::items)]
+#[diesel(treat_none_as_null = true)]
+#[serde(crate = "rocket::serde")]
+pub struct Bibliography {
+    /// comma separated, e.g. "Ashish Vaswani, Noam Shazeer"
+    pub authors: Option<String>,
+    pub year: Option<i32>,
+    pub venue: Option<String>,
+    pub doi: Option<String>,
+    pub arxiv_id: Option<String>,
+    pub url: Option<String>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|v| !v.is_empty())
+}
+
+// links are written as `<...>` destinations in the markdown
+fn is_link_safe(link: &str) -> bool {
+    !link
+        .chars()
+        .any(|c| c.is_whitespace() || c == '<' || c == '>' || c == '\\')
+}
+
+impl Bibliography {
+    /// Trims all fields and accepts DOIs and arXiv IDs also as links.
+    ///
+    /// Identifiers and links are checked, so they can be used in the generated markdown.
+    pub fn normalized(self) -> Result<Bibliography> {
+        let doi = non_empty(self.doi).and_then(|doi| duplicate::clean_doi(&doi));
+        let arxiv_id = non_empty(self.arxiv_id).and_then(|id| duplicate::clean_arxiv(&id));
+        if let Some(doi) = &doi {
+            if !doi.starts_with("10.") || !doi.contains('/') || !is_link_safe(doi) {
+                return Err(Error::msg(format!("Not a DOI: {}", doi)));
+            }
+        }
+        if let Some(id) = &arxiv_id {
+            if !duplicate::is_arxiv_id(id) {
+                return Err(Error::msg(format!("Not an arXiv ID: {}", id)));
+            }
+        }
+        let url = non_empty(self.url);
+        if let Some(url) = &url {
+            if !(url.starts_with("https://") || url.starts_with("http://")) || !is_link_safe(url) {
+                return Err(Error::msg(format!("Not a http(s) link: {}", url)));
+            }
+        }
+        if let Some(year) = self.year {
+            if !(1000..=9999).contains(&year) {
+                return Err(Error::msg(format!("Invalid year: {}", year)));
+            }
+        }
+
+        Ok(Bibliography {
+            authors: non_empty(self.authors),
+            year: self.year,
+            venue: non_empty(self.venue),
+            doi,
+            arxiv_id,
+            url,
+        })
+    }
+
+    pub fn author_list(&self) -> Vec<&str> {
+        self.authors
+            .as_deref()
+            .map(|a| {
+                a.split(',')
+                    .map(str::trim)
+                    .filter(|a| !a.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Named links to the paper, e.g. `("DOI", "https://doi.org/...")`.
+    pub fn links(&self) -> Vec<(&'static str, String)> {
+        let mut links = Vec::new();
+        if let Some(doi) = &self.doi {
+            links.push(("DOI", format!("https://doi.org/{}", doi)));
+        }
+        if let Some(id) = &self.arxiv_id {
+            links.push(("arXiv", format!("https://arxiv.org/abs/{}", id)));
+        }
+        if let Some(url) = &self.url {
+            links.push(("Link", url.clone()));
+        }
+        links
+    }
+
+    fn header(&self) -> String {
+        let mut lines = Vec::new();
+        let authors = self.authors.as_deref().map(markdown::escape);
+        match (authors, self.year) {
+            (Some(authors), Some(year)) => lines.push(format!("{}, {}", authors, year)),
+            (Some(authors), None) => lines.push(authors),
+            (None, Some(year)) => lines.push(year.to_string()),
+            (None, None) => (),
+        }
+        if let Some(venue) = &self.venue {
+            lines.push(format!("*{}*", markdown::escape(venue)));
+        }
+        let links = self.links();
+        if !links.is_empty() {
+            lines.push(
+                links
+                    .iter()
+                    .map(|(name, link)| format!("[{}](<{}>)", name, link))
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+            );
+        }
+        lines.join("\n\n")
+    }
+
+    /// Markdown of the card of an item with the given notes.
+    pub fn compose(&self, notes: &str) -> String {
+        let header = self.header();
+        if header.is_empty() {
+            notes.to_string()
+        } else {
+            format!("{}\n\n-------\n{}", header, notes)
+        }
+    }
+
+    pub fn render(&self, notes: &str) -> Result<String> {
+        markdown_to_html(&self.compose(notes))
+    }
+}
@@ -0,0 +1,157 @@
+use super::*;
+
+use anyhow::Error;
+use chrono::{NaiveDateTime, Utc};
+use item::Forbidden;
+use std::collections::HashMap;
+use user::User;
+
+#[derive(Serialize, Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = self::schema::comments)]
+#[serde(crate = "rocket::serde")]
+pub struct Comment {
+    pub id: i32,
+    pub item_id: i32,
+    pub user_id: i32,
+    pub markdown: String,
+    pub html: String,
+    pub created_at: NaiveDateTime,
+    pub edited_at: Option<NaiveDateTime>,
+    /// username of the author, taken from the joined users table
+    #[diesel(select_expression = self::schema::users::username)]
+    #[diesel(select_expression_type = self::schema::users::username)]
+    pub author: String,
+}
+
+#[derive(FromForm)]
+pub struct NewCommentData {
+    pub item: i32,
+    pub markdown: String,
+    pub back: String,
+}
+
+#[derive(FromForm)]
+pub struct ChangeCommentData {
+    pub id: i32,
+    pub markdown: String,
+    pub back: String,
+}
+
+#[derive(FromForm)]
+pub struct DeleteCommentData {
+    pub id: i32,
+    pub back: String,
+}
+
+impl Comment {
+    /// Comments of the given items grouped by item id, oldest first.
+    pub async fn for_items(ids: Vec<i32>, conn: &DbConn) -> HashMap<i32, Vec<Comment>> {
+        use self::schema::comments::dsl::{comments, created_at, item_id as comment_item_id};
+
+        conn.run(move |c| {
+            comments
+                .inner_join(all_users)
+                .filter(comment_item_id.eq_any(ids))
+                .order(created_at.asc())
+                .select(Comment::as_select())
+                .load::<Comment>(c)
+                .unwrap_or_default()
+                .into_iter()
+                .fold(HashMap::new(), |mut map, comment| {
+                    map.entry(comment.item_id)
+                        .or_insert_with(Vec::new)
+                        .push(comment);
+                    map
+                })
+        })
+        .await
+    }
+
+    pub async fn add(
+        id: i32,
+        uid: i32,
+        markdown: String,
+        html: String,
+        conn: &DbConn,
+    ) -> Result<()> {
+        use self::schema::comments::dsl as comments;
+
+        conn.run(move |c| {
+            let exists = all_items
+                .filter(item_id.eq(id))
+                .count()
+                .get_result::<i64>(c)
+                .context("Failed to read item from db.")?;
+            if exists == 0 {
+                return Err(Error::msg("Item not found in db."));
+            }
+
+            diesel::insert_into(comments::comments)
+                .values((
+                    comments::item_id.eq(id),
+                    comments::user_id.eq(uid),
+                    comments::markdown.eq(markdown),
+                    comments::html.eq(html),
+                    comments::created_at.eq(Utc::now().naive_utc()),
+                ))
+                .execute(c)
+                .context("Failed inserting comment into db.")?;
+            Ok(())
+        })
+        .await
+    }
+
+    // authors can change their own comments, admins moderate all comments
+    fn check_author(id: i32, uid: i32, is_admin: bool, c: &mut SqliteConnection) -> Result<()> {
+        use self::schema::comments::dsl::{comments, id as comment_id, user_id as author_id};
+
+        let author = comments
+            .filter(comment_id.eq(id))
+            .select(author_id)
+            .get_result::<i32>(c)
+            .context("Comment not found in db.")?;
+        if author != uid && !is_admin {
+            return Err(Forbidden("You are not allowed to change this comment.").into());
+        }
+        Ok(())
+    }
+
+    pub async fn update(
+        id: i32,
+        user: &User,
+        markdown: String,
+        html: String,
+        conn: &DbConn,
+    ) -> Result<()> {
+        use self::schema::comments::dsl as comments;
+
+        let (uid, is_admin) = (user.id, user.is_admin);
+        conn.run(move |c| {
+            Comment::check_author(id, uid, is_admin, c)?;
+            diesel::update(comments::comments.filter(comments::id.eq(id)))
+                .set((
+                    comments::markdown.eq(markdown),
+                    comments::html.eq(html),
+                    comments::edited_at.eq(Utc::now().naive_utc()),
+                ))
+                .execute(c)
+                .context("Failed to update comment in db.")?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn delete(id: i32, user: &User, conn: &DbConn) -> Result<()> {
+        use self::schema::comments::dsl::{comments, id as comment_id};
+
+        let (uid, is_admin) = (user.id, user.is_admin);
+        conn.run(move |c| {
+            Comment::check_author(id, uid, is_admin, c)?;
+            diesel::delete(comments.filter(comment_id.eq(id)))
+                .execute(c)
+                .context("Failed to delete comment from db.")?;
+            Ok(())
+        })
+        .await
+    }
+}
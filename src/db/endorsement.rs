@@ -0,0 +1,118 @@
+use super::*;
+
+use self::schema::endorsements::dsl::{
+    endorsed_at, endorsements, item_id as endorsement_item_id, user_id as endorsement_user_id,
+};
+use anyhow::Error;
+use chrono::Utc;
+use diesel::dsl::count_star;
+use item::Item;
+use status::ItemStatus;
+use std::collections::HashMap;
+
+/// How endorsements affect the ballot, configured in `Rocket.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde", default)]
+pub struct EndorsementConfig {
+    /// open items need this many endorsements to appear on the ranked ballot, 0 to show all
+    pub endorsements_to_rank: u32,
+}
+
+impl EndorsementConfig {
+    /// Whether an item with `count` endorsements appears on the ranked ballot.
+    pub fn is_ranked(&self, count: i64) -> bool {
+        count >= self.endorsements_to_rank as i64
+    }
+}
+
+#[derive(FromForm)]
+pub struct EndorseData {
+    pub item: i32,
+    /// `false` withdraws the endorsement
+    pub endorse: bool,
+}
+
+/// A "+1" of a user for an open item, independent of their ranking.
+pub struct Endorsement;
+
+impl Endorsement {
+    /// Number of endorsements by item id, items without endorsements are missing.
+    pub async fn counts(conn: &DbConn) -> HashMap<i32, i64> {
+        conn.run(Endorsement::counts_in).await
+    }
+
+    pub(crate) fn counts_in(c: &mut SqliteConnection) -> HashMap<i32, i64> {
+        endorsements
+            .group_by(endorsement_item_id)
+            .select((endorsement_item_id, count_star()))
+            .load::<(i32, i64)>(c)
+            .unwrap_or_default()
+            .into_iter()
+            .collect()
+    }
+
+    /// Ids of the items endorsed by `uid`.
+    pub async fn of_user(uid: i32, conn: &DbConn) -> Vec<i32> {
+        conn.run(move |c| {
+            endorsements
+                .filter(endorsement_user_id.eq(uid))
+                .select(endorsement_item_id)
+                .load::<i32>(c)
+                .unwrap_or_default()
+        })
+        .await
+    }
+
+    /// Endorses item `id` for `uid` or withdraws the endorsement, only open items can be endorsed.
+    pub async fn set(id: i32, uid: i32, endorse: bool, conn: &DbConn) -> Result<()> {
+        if endorse {
+            let item = Item::from_id(id, conn)
+                .await
+                .ok_or(Error::msg("Could not retrieve item from database"))?;
+            if item.status != ItemStatus::Open {
+                return Err(Error::msg("Only open items can be endorsed."));
+            }
+        }
+
+        conn.run(move |c| {
+            if endorse {
+                diesel::insert_or_ignore_into(endorsements)
+                    .values((
+                        endorsement_user_id.eq(uid),
+                        endorsement_item_id.eq(id),
+                        endorsed_at.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(c)
+                    .context("Failed to save endorsement in db.")?;
+            } else {
+                diesel::delete(
+                    endorsements
+                        .filter(endorsement_user_id.eq(uid))
+                        .filter(endorsement_item_id.eq(id)),
+                )
+                .execute(c)
+                .context("Failed to delete endorsement from db.")?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Moves the endorsements of item `drop` to `keep`, used when merging items.
+    pub(crate) fn merge(keep: i32, drop: i32, c: &mut SqliteConnection) -> Result<()> {
+        let endorsed_keep = endorsements
+            .filter(endorsement_item_id.eq(keep))
+            .select(endorsement_user_id)
+            .load::<i32>(c)
+            .context("Failed to move endorsements.")?;
+        diesel::update(
+            endorsements
+                .filter(endorsement_item_id.eq(drop))
+                .filter(endorsement_user_id.ne_all(endorsed_keep)),
+        )
+        .set(endorsement_item_id.eq(keep))
+        .execute(c)
+        .context("Failed to move endorsements.")?;
+        Ok(())
+    }
+}
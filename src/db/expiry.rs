@@ -0,0 +1,148 @@
+use super::*;
+
+use anyhow::Error;
+use chrono::{Months, NaiveDateTime, Utc};
+use diesel::dsl::max;
+use item::{Forbidden, Item};
+use notification::Notification;
+use status::{ItemStatus, StatusChange};
+use std::collections::HashMap;
+use user::User;
+
+/// When open items expire, configured in `Rocket.toml`. Unset rules never expire an item.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ExpiryConfig {
+    /// expire items nobody ranked for this many months
+    pub expire_unvoted_months: Option<u32>,
+    /// expire items proposed (or revived) more than this many months ago
+    pub expire_proposed_months: Option<u32>,
+}
+
+#[derive(FromForm)]
+pub struct ReviveData {
+    pub id: i32,
+}
+
+fn months_ago(now: NaiveDateTime, months: Option<u32>) -> Option<NaiveDateTime> {
+    months
+        .filter(|m| *m > 0)
+        .and_then(|m| now.checked_sub_months(Months::new(m)))
+}
+
+impl Item {
+    // see `Item::stale`, also used inside of the transaction of `Item::expire_stale`
+    fn stale_in(config: &ExpiryConfig, c: &mut SqliteConnection) -> Result<Vec<(Item, String)>> {
+        use self::schema::status_changes::dsl::{
+            changed_at, item_id as change_item_id, new_status, status_changes,
+        };
+        use self::schema::votes::dsl::voted_at;
+
+        let now = Utc::now().naive_utc();
+        let unvoted_before = months_ago(now, config.expire_unvoted_months);
+        let proposed_before = months_ago(now, config.expire_proposed_months);
+        if unvoted_before.is_none() && proposed_before.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let items = Item::query()
+            .filter(item_status.eq(ItemStatus::Open))
+            .load::<Item>(c)
+            .context("Failed to retrieve items form db.")?;
+        let last_votes: HashMap<i32, Option<NaiveDateTime>> = all_votes
+            .group_by(vote_item_id)
+            .select((vote_item_id, max(voted_at)))
+            .load::<(i32, Option<NaiveDateTime>)>(c)
+            .context("Failed to read ballots from db.")?
+            .into_iter()
+            .collect();
+        let revived: HashMap<i32, Option<NaiveDateTime>> = status_changes
+            .filter(new_status.eq(ItemStatus::Open))
+            .group_by(change_item_id)
+            .select((change_item_id, max(changed_at)))
+            .load::<(i32, Option<NaiveDateTime>)>(c)
+            .context("Failed to read status changes from db.")?
+            .into_iter()
+            .collect();
+
+        Ok(items
+            .into_iter()
+            .filter_map(|item| {
+                // nothing is known about old items which were never revived or ranked
+                let since = revived
+                    .get(&item.id)
+                    .copied()
+                    .flatten()
+                    .max(item::known_creation(item.created_at));
+                let last_vote = last_votes.get(&item.id).copied().flatten();
+                let active = last_vote.max(since)?;
+
+                let reason = if proposed_before.is_some_and(|t| since.is_some_and(|s| s < t)) {
+                    format!(
+                        "it was proposed more than {} months ago",
+                        config.expire_proposed_months.unwrap_or_default()
+                    )
+                } else if unvoted_before.is_some_and(|t| active < t) {
+                    format!(
+                        "nobody ranked it for {} months",
+                        config.expire_unvoted_months.unwrap_or_default()
+                    )
+                } else {
+                    return None;
+                };
+                Some((item, reason))
+            })
+            .collect())
+    }
+
+    /// Open items which expire under `config` together with the reason.
+    ///
+    /// An item is as old as its proposal or its last revival, whichever is later.
+    pub async fn stale(config: &ExpiryConfig, conn: &DbConn) -> Result<Vec<(Item, String)>> {
+        let config = config.clone();
+        conn.run(move |c| Item::stale_in(&config, c)).await
+    }
+
+    /// Marks all stale items as expired and notifies their proposers.
+    ///
+    /// The items are selected in the same transaction, so items which were scheduled or ranked in
+    /// the meantime are left alone.
+    pub async fn expire_stale(config: &ExpiryConfig, conn: &DbConn) -> Result<Vec<(Item, String)>> {
+        let config = config.clone();
+        conn.run(move |c| {
+            c.transaction(|c| {
+                let stale = Item::stale_in(&config, c)?;
+                for (item, reason) in &stale {
+                    StatusChange::apply(item.id, ItemStatus::Expired, c)?;
+                    if let Some(uid) = item.proposed_by {
+                        let message = format!(
+                            "Your item \"{}\" expired because {}. Revive it to put it back on the ballot.",
+                            item.title, reason
+                        );
+                        Notification::send(uid, Some(item.id), message, c)?;
+                    }
+                }
+                Ok::<_, Error>(stale)
+            })
+        })
+        .await
+    }
+
+    /// Puts an expired item back on the ballot, allowed for admins and its proposer.
+    pub async fn revive(id: i32, user: &User, conn: &DbConn) -> Result<()> {
+        let item = Item::from_id(id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        if !user.is_admin && item.proposed_by != Some(user.id) {
+            return Err(Forbidden("You are not allowed to revive this item.").into());
+        }
+        if item.status != ItemStatus::Expired {
+            return Err(Error::msg(format!("Item {} is not expired.", id)));
+        }
+        conn.run(move |c| {
+            c.transaction(|c| StatusChange::apply(id, ItemStatus::Open, c))?;
+            Ok(())
+        })
+        .await
+    }
+}
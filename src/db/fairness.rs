@@ -0,0 +1,109 @@
+use super::*;
+
+use chrono::{Datelike, NaiveDate, Utc};
+use item::Item;
+use status::ItemStatus;
+
+/// Rules which keep proposers from winning too often, configured in `Rocket.toml`.
+///
+/// An item wins when it is scheduled for a meeting, so every scheduled, discussed or follow-up
+/// item with a date counts as a win of its proposer. Items without a proposer are always eligible.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde", default)]
+pub struct FairnessConfig {
+    /// the proposer of the last winner cannot win the next election
+    pub no_consecutive_wins: bool,
+    /// wins per proposer within the current semester, unset for no limit
+    pub max_wins_per_semester: Option<u32>,
+}
+
+/// First day of the semester containing `date`, summer semesters start on April 1st and winter
+/// semesters on October 1st.
+pub fn semester_start(date: NaiveDate) -> NaiveDate {
+    let (year, month) = match date.month() {
+        1..=3 => (date.year() - 1, 10),
+        4..=9 => (date.year(), 4),
+        _ => (date.year(), 10),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1).unwrap_or(date)
+}
+
+fn won(c: &mut SqliteConnection) -> Result<Vec<(Option<i32>, String, NaiveDate)>> {
+    let wins = all_items
+        .filter(item_status.eq_any([
+            ItemStatus::Scheduled,
+            ItemStatus::Discussed,
+            ItemStatus::FollowUp,
+        ]))
+        .filter(item_discussed_on.is_not_null())
+        .order(item_discussed_on.desc())
+        .select((
+            item_proposed_by,
+            self::schema::items::title,
+            item_discussed_on,
+        ))
+        .load::<(Option<i32>, String, Option<NaiveDate>)>(c)
+        .context("Failed to read items from db.")?;
+    Ok(wins
+        .into_iter()
+        .filter_map(|(proposer, title, date)| Some((proposer, title, date?)))
+        .collect())
+}
+
+impl FairnessConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.no_consecutive_wins || self.max_wins_per_semester.is_some()
+    }
+
+    /// Why `item` may not win the election right now, `None` if it may.
+    pub(crate) fn check(&self, item: &Item, c: &mut SqliteConnection) -> Result<Option<String>> {
+        let Some(proposer) = item.proposed_by.filter(|_| self.is_enabled()) else {
+            return Ok(None);
+        };
+        let name = item.proposer.as_deref().unwrap_or("its proposer");
+        let wins = won(c)?;
+
+        if self.no_consecutive_wins {
+            if let Some((_, title, date)) = wins.first().filter(|(p, ..)| *p == Some(proposer)) {
+                return Ok(Some(format!(
+                    "{} also proposed the previous winner \"{}\" ({}).",
+                    name, title, date
+                )));
+            }
+        }
+
+        if let Some(max) = self.max_wins_per_semester {
+            let start = semester_start(Utc::now().naive_utc().into());
+            let count = wins
+                .iter()
+                .filter(|(p, _, date)| *p == Some(proposer) && *date >= start)
+                .count();
+            if count >= max as usize {
+                return Ok(Some(format!(
+                    "{} reached the limit of {} wins this semester (since {}).",
+                    name, max, start
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn semester_boundaries() {
+        assert_eq!(semester_start(date(2024, 3, 31)), date(2023, 10, 1));
+        assert_eq!(semester_start(date(2024, 4, 1)), date(2024, 4, 1));
+        assert_eq!(semester_start(date(2024, 9, 30)), date(2024, 4, 1));
+        assert_eq!(semester_start(date(2024, 10, 1)), date(2024, 10, 1));
+        assert_eq!(semester_start(date(2024, 12, 31)), date(2024, 10, 1));
+        assert_eq!(semester_start(date(2025, 1, 1)), date(2024, 10, 1));
+    }
+}
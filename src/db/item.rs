@@ -3,23 +3,93 @@ use crate::mail;
 use super::*;
 
 use anyhow::Error;
-use chrono::{NaiveDate, Utc};
+use attachment::{Attachment, AttachmentConfig};
+use attendance::Attendance;
+use bibliography::Bibliography;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::dsl::sql;
+use diesel::dsl::{AsSelect, IntoBoxed, LeftJoin, Select};
+use diesel::sql_types::Integer;
+use diesel::sqlite::Sqlite;
+use endorsement::Endorsement;
+use limits::SubmissionLimits;
+use notification::Notification;
+use presenter::Presenter;
+use revision::Revision;
+use status::{ItemStatus, StatusChange};
+use std::fmt;
+use tag::Tag;
+use user::User;
 
-#[derive(Serialize, Queryable, Debug, Clone)]
+#[derive(Serialize, Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = self::schema::items)]
 pub struct Item {
     pub id: i32,
     pub title: String,
     pub html: String,
     pub markdown: String,
     pub discussed_on: Option<NaiveDate>,
+    pub status: ItemStatus,
+    pub proposed_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    #[diesel(embed)]
+    #[serde(flatten)]
+    pub bib: Bibliography,
+    /// username of the proposer, taken from the joined users table
+    #[diesel(select_expression = self::schema::users::username.nullable())]
+    #[diesel(select_expression_type = diesel::dsl::Nullable<self::schema::users::username>)]
+    pub proposer: Option<String>,
+}
+
+/// `None` for the creation time of items added before it was recorded, which `upgrade.sql` sets
+/// to the start of 1970.
+pub fn known_creation(created_at: NaiveDateTime) -> Option<NaiveDateTime> {
+    (created_at.and_utc().timestamp() > 0).then_some(created_at)
 }
 
-#[derive(FromForm, Insertable)]
+/// Criteria for `Item::from_ids` in addition to the list of ids.
+#[derive(Default, Debug)]
+pub struct ItemFilter {
+    pub discussed_only: bool,
+    pub undiscussed_only: bool,
+    pub statuses: Vec<ItemStatus>,
+    /// leave out items waiting for moderation, independent of `statuses`
+    pub exclude_pending: bool,
+    pub proposed_by: Option<i32>,
+    /// only items discussed on or after this date
+    pub discussed_from: Option<NaiveDate>,
+    /// only items discussed on or before this date
+    pub discussed_to: Option<NaiveDate>,
+    /// only items with this tag
+    pub tag: Option<String>,
+}
+
+type ItemQuery<'a> = IntoBoxed<
+    'a,
+    Select<
+        LeftJoin<self::schema::items::table, self::schema::users::table>,
+        AsSelect<Item, Sqlite>,
+    >,
+    Sqlite,
+>;
+
+#[derive(FromForm, Insertable, Serialize)]
 #[diesel(table_name = self::schema::items)]
 pub struct NewItemData {
     pub title: String,
     pub html: String,
+    /// free-form notes shown below the bibliographic fields
     pub markdown: String,
+    #[diesel(embed)]
+    #[serde(flatten)]
+    pub bib: Bibliography,
+    /// comma separated, see `tag::parse`
+    #[field(default = "")]
+    #[diesel(skip_insertion)]
+    pub tags: String,
+    /// submit even if likely duplicates exist
+    #[diesel(skip_insertion)]
+    pub confirm: bool,
 }
 
 #[derive(FromForm)]
@@ -28,7 +98,60 @@ pub struct ChangeItemData {
     pub title: String,
     pub html: String,
     pub markdown: String,
-    pub discussed_on: String,
+    pub bib: Bibliography,
+    /// comma separated, see `tag::parse`
+    pub tags: String,
+    /// only submitted by admins, `None` keeps the current date
+    pub discussed_on: Option<String>,
+}
+
+impl NewItemData {
+    /// Normalizes the bibliographic fields and renders the card.
+    pub fn render(&mut self) -> Result<()> {
+        self.bib = std::mem::take(&mut self.bib).normalized()?;
+        self.html = self.bib.render(&self.markdown)?;
+        Ok(())
+    }
+}
+
+impl ChangeItemData {
+    /// Normalizes the bibliographic fields and renders the card.
+    pub fn render(&mut self) -> Result<()> {
+        self.bib = std::mem::take(&mut self.bib).normalized()?;
+        self.html = self.bib.render(&self.markdown)?;
+        Ok(())
+    }
+}
+
+/// Content of the item editor to be rendered without saving it.
+#[derive(FromForm)]
+pub struct PreviewData {
+    pub markdown: String,
+    pub bib: Bibliography,
+}
+
+/// Error returned when a user tries to change an item they are not allowed to change.
+#[derive(Debug)]
+pub struct Forbidden(pub &'static str);
+
+impl fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for Forbidden {}
+
+#[derive(FromForm)]
+pub struct ChangeStatusData {
+    pub id: i32,
+    pub status: ItemStatus,
+}
+
+#[derive(FromForm)]
+pub struct MergeItemsData {
+    pub keep: i32,
+    pub drop: i32,
 }
 
 #[derive(FromForm)]
@@ -48,26 +171,36 @@ pub enum ItemFormat {
 }
 
 impl Item {
+    /// Items joined with their proposer so they can be loaded as `Item`.
+    pub(crate) fn query<'a>() -> ItemQuery<'a> {
+        all_items
+            .left_join(all_users)
+            .select(Item::as_select())
+            .into_boxed()
+    }
+
     pub async fn get_user_and_votes(uid: i32, conn: &DbConn) -> Vec<(Item, Option<i32>)> {
         conn.run(move |c| {
             all_items
                 .left_join(
                     all_votes.on(vote_user_id
-                        .eq(&uid)
+                        .eq(uid)
                         .and(vote_item_id.eq(self::schema::items::id))),
                 )
-                .filter(item_discussed_on.is_null())
+                .left_join(all_users.on(item_proposed_by.eq(user_id.nullable())))
+                .filter(item_status.eq(ItemStatus::Open))
                 .order((vote_user_id.desc(), ordinal.asc()))
-                .select((self::schema::items::all_columns, ordinal.nullable()))
+                .select((Item::as_select(), ordinal.nullable()))
                 .load::<(Item, Option<i32>)>(c)
-                .unwrap_or(Vec::new())
+                .unwrap_or_default()
         })
         .await
     }
 
     pub async fn get_decided(conn: &DbConn) -> Option<Item> {
         conn.run(move |c| {
-            let item = all_items
+            let item = Item::query()
+                .filter(item_status.eq(ItemStatus::Scheduled))
                 .filter(item_discussed_on.is_not_null())
                 .order(item_discussed_on.desc())
                 .limit(1)
@@ -79,30 +212,35 @@ impl Item {
             if today <= item_date {
                 return Some(item);
             }
-            return None;
+            None
         })
         .await
     }
 
     pub async fn get_history(conn: &DbConn) -> Vec<Item> {
         conn.run(move |c| {
-            all_items
+            Item::query()
                 .filter(item_discussed_on.is_not_null())
+                .filter(item_status.eq_any([
+                    ItemStatus::Scheduled,
+                    ItemStatus::Discussed,
+                    ItemStatus::FollowUp,
+                ]))
                 .order(item_discussed_on.desc())
                 .load::<Item>(c)
-                .unwrap_or(Vec::new())
+                .unwrap_or_default()
         })
         .await
     }
 
     pub async fn get_all(conn: &DbConn) -> Vec<Item> {
-        conn.run(move |c| all_items.load::<Item>(c).unwrap_or(Vec::new()))
+        conn.run(move |c| Item::query().load::<Item>(c).unwrap_or_default())
             .await
     }
 
     pub async fn from_id(id: i32, conn: &DbConn) -> Option<Item> {
         conn.run(move |c| {
-            let item = all_items
+            let item = Item::query()
                 .filter(item_id.eq(id))
                 .get_result::<Item>(c)
                 .ok()?;
@@ -111,87 +249,339 @@ impl Item {
         .await
     }
 
-    pub async fn from_ids(
-        ids: Vec<i32>,
-        discussed_only: bool,
-        undiscussed_only: bool,
-        conn: &DbConn,
-    ) -> Result<Vec<Item>> {
+    pub async fn from_ids(ids: Vec<i32>, filter: ItemFilter, conn: &DbConn) -> Result<Vec<Item>> {
         conn.run(move |c| {
-            let mut query = all_items.into_boxed();
-            if ids.len() > 0 {
+            let mut query = Item::query();
+            if !ids.is_empty() {
                 query = query.filter(item_id.eq_any(ids));
             }
-            if discussed_only {
+            if filter.discussed_only {
                 query = query.filter(item_discussed_on.is_not_null())
-            } else if undiscussed_only {
+            } else if filter.undiscussed_only {
                 query = query.filter(item_discussed_on.is_null())
             }
+            if !filter.statuses.is_empty() {
+                query = query.filter(item_status.eq_any(filter.statuses));
+            }
+            if filter.exclude_pending {
+                query = query.filter(item_status.ne(ItemStatus::Pending));
+            }
+            if let Some(uid) = filter.proposed_by {
+                query = query.filter(item_proposed_by.eq(uid));
+            }
+            if let Some(from) = filter.discussed_from {
+                query = query.filter(item_discussed_on.ge(from));
+            }
+            if let Some(to) = filter.discussed_to {
+                query = query.filter(item_discussed_on.le(to));
+            }
+            if let Some(tag) = filter.tag {
+                query = query.filter(item_id.eq_any(Tag::items_with(&tag, c)?));
+            }
 
             let items = query.get_results::<Item>(c);
 
-            Ok(items.context("Failed to retrieve items form db.")?)
+            items.context("Failed to retrieve items form db.")
         })
         .await
     }
 
-    pub async fn add(item_data: NewItemData, conn: &DbConn) -> Result<()> {
+    /// Adds a new item which is either `Open` or `Pending` until an admin approved it.
+    ///
+    /// Fails if the proposer exceeds the `limits`.
+    pub async fn add(
+        item_data: NewItemData,
+        proposer: Option<i32>,
+        status: ItemStatus,
+        limits: &SubmissionLimits,
+        conn: &DbConn,
+    ) -> Result<()> {
+        let limits = limits.clone();
         conn.run(move |c| {
-            diesel::insert_into(all_items)
-                .values(&item_data)
-                .execute(c)
-                .context("Failed inserting new item into db.")?;
-            Ok(())
+            c.transaction(|c| {
+                if let Some(uid) = proposer {
+                    limits.check(uid, c)?;
+                }
+                let now = Utc::now().naive_utc();
+                diesel::insert_into(all_items)
+                    .values((
+                        &item_data,
+                        item_proposed_by.eq(proposer),
+                        item_created_at.eq(now),
+                        item_status.eq(status),
+                    ))
+                    .execute(c)
+                    .context("Failed inserting new item into db.")?;
+                let id = diesel::select(sql::<Integer>("last_insert_rowid()"))
+                    .get_result::<i32>(c)
+                    .context("Failed inserting new item into db.")?;
+                Tag::set_for_item(id, &tag::parse(&item_data.tags), c)?;
+                if status == ItemStatus::Pending {
+                    let message = format!("New item \"{}\" awaits moderation.", item_data.title);
+                    Notification::send_to_admins(Some(id), message, c)?;
+                }
+                Revision::record(id, proposer, now, c)
+            })
         })
         .await
     }
 
-    pub async fn update(item_data: ChangeItemData, conn: &DbConn) -> Result<()> {
+    /// Admins can edit every item, proposers only their own items while they are open or pending.
+    pub fn can_edit(&self, user: &User) -> bool {
+        user.is_admin
+            || (self.proposed_by == Some(user.id)
+                && matches!(self.status, ItemStatus::Open | ItemStatus::Pending))
+    }
+
+    pub async fn update(item_data: ChangeItemData, user: &User, conn: &DbConn) -> Result<()> {
         use self::schema::items::dsl::{html, markdown, title};
-        use std::ops::Not;
 
+        let item = Item::from_id(item_data.id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        if !item.can_edit(user) {
+            return Err(Forbidden("You are not allowed to edit this item.").into());
+        }
+
+        let discussed = match item_data.discussed_on.as_deref() {
+            None => None,
+            Some("") => Some(None),
+            Some(d) => Some(Some(
+                NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                    .with_context(|| format!("Invalid date: {}", d))?,
+            )),
+        };
+        if !user.is_admin && discussed.is_some_and(|d| d != item.discussed_on) {
+            return Err(Forbidden("Only admins can change the date of an item.").into());
+        }
+
+        let uid = user.id;
         conn.run(move |c| {
-            let discussed_on = &item_data.discussed_on;
-            let discussed = discussed_on.is_empty().not().then(|| discussed_on);
-            diesel::update(all_items.filter(item_id.eq(item_data.id)))
-                .set((
-                    title.eq(item_data.title),
-                    html.eq(item_data.html),
-                    markdown.eq(item_data.markdown),
-                    item_discussed_on.eq(discussed),
-                ))
-                .execute(c)
-                .context("Failed inserting new item into db.")?;
-            Ok(())
+            c.transaction(|c| {
+                Revision::ensure_initial(item_data.id, c)?;
+                diesel::update(all_items.filter(item_id.eq(item_data.id)))
+                    .set((
+                        title.eq(item_data.title),
+                        html.eq(item_data.html),
+                        markdown.eq(item_data.markdown),
+                        &item_data.bib,
+                    ))
+                    .execute(c)
+                    .context("Failed inserting new item into db.")?;
+                Tag::set_for_item(item_data.id, &tag::parse(&item_data.tags), c)?;
+                Revision::record_now(item_data.id, uid, c)?;
+                match discussed {
+                    Some(date) => Item::schedule(item_data.id, date, c),
+                    None => Ok(()),
+                }
+            })
         })
         .await
     }
 
-    pub async fn delete(ids: Vec<i32>, conn: &DbConn) -> Result<usize> {
+    /// Restores title and content of revision `rev` and stores that as a new revision.
+    pub async fn rollback(id: i32, rev: i32, uid: i32, conn: &DbConn) -> Result<()> {
+        use self::schema::items::dsl::{html, markdown, title};
+        use self::schema::revisions::dsl::{
+            id as revision_id, item_id as revision_item_id, revisions,
+        };
+
         conn.run(move |c| {
-            let rows = diesel::delete(all_items.filter(item_id.eq_any(ids)))
+            c.transaction(|c| {
+                let old = revisions
+                    .left_join(all_users)
+                    .filter(revision_id.eq(rev))
+                    .filter(revision_item_id.eq(id))
+                    .select(Revision::as_select())
+                    .get_result::<Revision>(c)
+                    .context("Revision not found for this item.")?;
+
+                Revision::ensure_initial(id, c)?;
+                diesel::update(all_items.filter(item_id.eq(id)))
+                    .set((
+                        title.eq(&old.title),
+                        markdown.eq(&old.markdown),
+                        html.eq(&old.html),
+                        &old.bib(),
+                    ))
+                    .execute(c)
+                    .context("Failed to update item in db.")?;
+                Revision::record_now(id, uid, c)
+            })
+        })
+        .await
+    }
+
+    /// Merges item `drop` into item `keep` and deletes `drop`.
+    ///
+    /// Ballots, endorsements, volunteers, the presenter, attendance, comments, attachments, tags,
+    /// ratings and meeting notes of `drop` are moved to `keep`. Only open or pending items can be
+    /// dropped.
+    pub async fn merge(keep: i32, drop: i32, conn: &DbConn) -> Result<()> {
+        use self::schema::{attachments, comments, meeting_notes, ratings};
+
+        if keep == drop {
+            return Err(Error::msg("Cannot merge an item with itself."));
+        }
+        conn.run(move |c| {
+            c.transaction(|c| {
+                let found = all_items
+                    .filter(item_id.eq_any([keep, drop]))
+                    .select((item_id, item_status))
+                    .load::<(i32, ItemStatus)>(c)
+                    .context("Failed to read items from db.")?;
+                if found.len() != 2 {
+                    return Err(Error::msg("Item not found in db."));
+                }
+                // the history of a scheduled or discussed item would be lost
+                if found.iter().any(|(id, s)| {
+                    *id == drop && !matches!(s, ItemStatus::Open | ItemStatus::Pending)
+                }) {
+                    return Err(Error::msg(
+                        "Only open or pending items can be merged into another item.",
+                    ));
+                }
+
+                vote::Vote::merge_ballots(keep, drop, c)?;
+                Endorsement::merge(keep, drop, c)?;
+                Presenter::merge(keep, drop, c)?;
+                Attendance::merge(keep, drop, c)?;
+
+                diesel::update(comments::table.filter(comments::item_id.eq(drop)))
+                    .set(comments::item_id.eq(keep))
+                    .execute(c)
+                    .context("Failed to move comments.")?;
+                diesel::update(attachments::table.filter(attachments::item_id.eq(drop)))
+                    .set(attachments::item_id.eq(keep))
+                    .execute(c)
+                    .context("Failed to move attachments.")?;
+                Tag::copy(drop, keep, c)?;
+
+                // ratings of users who rated both items are dropped
+                let rated_keep = ratings::table
+                    .filter(ratings::item_id.eq(keep))
+                    .select(ratings::user_id)
+                    .load::<i32>(c)
+                    .context("Failed to move ratings.")?;
+                diesel::update(
+                    ratings::table
+                        .filter(ratings::item_id.eq(drop))
+                        .filter(ratings::user_id.ne_all(rated_keep)),
+                )
+                .set(ratings::item_id.eq(keep))
                 .execute(c)
-                .context("Failed to delete items from db.")?;
-            Ok(rows)
+                .context("Failed to move ratings.")?;
+
+                let keep_has_notes = meeting_notes::table
+                    .filter(meeting_notes::item_id.eq(keep))
+                    .count()
+                    .get_result::<i64>(c)
+                    .context("Failed to move meeting notes.")?
+                    > 0;
+                if !keep_has_notes {
+                    diesel::update(meeting_notes::table.filter(meeting_notes::item_id.eq(drop)))
+                        .set(meeting_notes::item_id.eq(keep))
+                        .execute(c)
+                        .context("Failed to move meeting notes.")?;
+                }
+
+                diesel::delete(all_items.filter(item_id.eq(drop)))
+                    .execute(c)
+                    .context("Failed to delete items from db.")?;
+                Tag::remove_unused(c)
+            })
         })
         .await
     }
 
+    /// Deletes the items together with the files attached to them.
+    pub async fn delete(ids: Vec<i32>, config: &AttachmentConfig, conn: &DbConn) -> Result<usize> {
+        let (rows, attachments) = conn
+            .run(move |c| {
+                c.transaction(|c| {
+                    let attachments = Attachment::of_items(&ids, c)?;
+                    let rows = diesel::delete(all_items.filter(item_id.eq_any(ids)))
+                        .execute(c)
+                        .context("Failed to delete items from db.")?;
+                    Tag::remove_unused(c)?;
+                    Ok::<_, Error>((rows, attachments))
+                })
+            })
+            .await?;
+        Attachment::remove_files(&attachments, config).await;
+        Ok(rows)
+    }
+
     pub async fn set_discussed(id: i32, date: Option<NaiveDate>, conn: &DbConn) -> Result<()> {
+        conn.run(move |c| c.transaction(|c| Item::schedule(id, date, c)))
+            .await
+    }
+
+    pub async fn set_status(id: i32, new_status: ItemStatus, conn: &DbConn) -> Result<()> {
         conn.run(move |c| {
-            diesel::update(all_items.filter(item_id.eq(id)))
-                .set(item_discussed_on.eq(date))
-                .execute(c)
-                .context("Failed inserting new item into db.")?;
-            Ok(())
+            c.transaction(|c| {
+                let date = all_items
+                    .filter(item_id.eq(id))
+                    .select(item_discussed_on)
+                    .get_result::<Option<NaiveDate>>(c)
+                    .context("Item not found in db.")?;
+                if new_status.has_date() && date.is_none() {
+                    return Err(Error::msg(format!(
+                        "Item must have a date to become {}.",
+                        new_status
+                    )));
+                }
+
+                StatusChange::apply(id, new_status, c)?;
+                if !new_status.has_date() {
+                    diesel::update(all_items.filter(item_id.eq(id)))
+                        .set(item_discussed_on.eq(None::<NaiveDate>))
+                        .execute(c)
+                        .context("Failed to update item in db.")?;
+                    Presenter::remove(id, c)?;
+                }
+                Ok(())
+            })
         })
         .await
     }
 
+    // setting a date schedules an item, removing it reopens the item for voting
+    fn schedule(id: i32, date: Option<NaiveDate>, c: &mut SqliteConnection) -> Result<()> {
+        let current = all_items
+            .filter(item_id.eq(id))
+            .select(item_status)
+            .get_result::<ItemStatus>(c)
+            .context("Item not found in db.")?;
+
+        match date {
+            Some(_) if current.has_date() => {}
+            Some(_) => {
+                StatusChange::apply(id, ItemStatus::Scheduled, c)?;
+            }
+            None if current.has_date() => {
+                StatusChange::apply(id, ItemStatus::Open, c)?;
+                Presenter::remove(id, c)?;
+            }
+            None => return Ok(()),
+        }
+
+        diesel::update(all_items.filter(item_id.eq(id)))
+            .set(item_discussed_on.eq(date))
+            .execute(c)
+            .context("Failed to schedule item.")?;
+        Ok(())
+    }
+
+    /// Markdown of the rendered card, i.e. the bibliographic fields followed by the notes.
+    pub fn card_markdown(&self) -> String {
+        self.bib.compose(&self.markdown)
+    }
+
     pub fn format(&self, item_format: ItemFormat) -> String {
         match item_format {
             ItemFormat::HTML => format!("<h3>{}</h3>\n{}", self.title, self.html),
-            ItemFormat::Markdown => format!("{}\n-------\n{}", self.title, self.markdown),
+            ItemFormat::Markdown => format!("{}\n-------\n{}", self.title, self.card_markdown()),
         }
     }
 
@@ -201,6 +591,7 @@ impl Item {
             .ok_or(Error::msg("Could not retrieve item from database"))?;
         mail::send(
             &item,
+            Presenter::for_item(item.id, conn).await,
             mail_data.from,
             mail_data.to,
             mail_data.comment,
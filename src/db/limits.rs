@@ -0,0 +1,139 @@
+use super::*;
+
+use anyhow::Error;
+use chrono::{Duration, Utc};
+use diesel::dsl::min;
+use status::ItemStatus;
+
+/// How many items users can propose, configured in `Rocket.toml`. Admins are exempt.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct SubmissionLimits {
+    /// open and pending items per user
+    pub max_open_items: Option<u32>,
+    /// new items per user within `submission_window_hours`
+    pub max_submissions: Option<u32>,
+    pub submission_window_hours: u32,
+}
+
+impl Default for SubmissionLimits {
+    fn default() -> SubmissionLimits {
+        SubmissionLimits {
+            max_open_items: None,
+            max_submissions: None,
+            submission_window_hours: 24,
+        }
+    }
+}
+
+impl SubmissionLimits {
+    /// Fails if user `uid` may not propose another item right now.
+    pub(crate) fn check(&self, uid: i32, c: &mut SqliteConnection) -> Result<()> {
+        let is_admin = all_users
+            .filter(user_id.eq(uid))
+            .select(user_admin)
+            .get_result::<bool>(c)
+            .context("User not found in db.")?;
+        if is_admin {
+            return Ok(());
+        }
+
+        if let Some(max) = self.max_open_items {
+            let open = all_items
+                .filter(item_proposed_by.eq(uid))
+                .filter(item_status.eq_any([ItemStatus::Open, ItemStatus::Pending]))
+                .count()
+                .get_result::<i64>(c)
+                .context("Failed to read items from db.")?;
+            if open >= max as i64 {
+                return Err(Error::msg(format!(
+                    "You already have {} open items, which is the limit. \
+                     New items can be added once one of them was discussed or withdrawn.",
+                    open
+                )));
+            }
+        }
+
+        if let Some(max) = self.max_submissions {
+            let window = Duration::hours(self.submission_window_hours as i64);
+            let since = Utc::now().naive_utc() - window;
+            let (count, oldest) = all_items
+                .filter(item_proposed_by.eq(uid))
+                .filter(item_created_at.ge(since))
+                .select((diesel::dsl::count_star(), min(item_created_at)))
+                .get_result::<(i64, Option<chrono::NaiveDateTime>)>(c)
+                .context("Failed to read items from db.")?;
+            if count >= max as i64 {
+                let retry = oldest.map(|t| t + window).unwrap_or_default();
+                return Err(Error::msg(format!(
+                    "You can add at most {} items within {} hours, please try again after {} UTC.",
+                    max,
+                    self.submission_window_hours,
+                    retry.format("%Y-%m-%d %H:%M")
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(c: &mut SqliteConnection) {
+        diesel::sql_query(
+            "INSERT INTO users (id, username, password, is_admin) \
+             VALUES (1, 'admin', 'x', true), (2, 'user', 'y', false)",
+        )
+        .execute(c)
+        .unwrap();
+    }
+
+    fn propose(uid: i32, status: &str, hours_ago: u32, c: &mut SqliteConnection) {
+        diesel::sql_query(format!(
+            "INSERT INTO items (title, html, markdown, proposed_by, status, created_at) \
+             VALUES ('a', '', '', {}, '{}', datetime('now', '-{} hours'))",
+            uid, status, hours_ago
+        ))
+        .execute(c)
+        .unwrap();
+    }
+
+    #[test]
+    fn max_open_items() {
+        let c = &mut test_connection();
+        setup(c);
+        let limits = SubmissionLimits {
+            max_open_items: Some(2),
+            ..SubmissionLimits::default()
+        };
+
+        propose(2, "open", 100, c);
+        propose(2, "discussed", 100, c);
+        assert!(limits.check(2, c).is_ok());
+        propose(2, "pending", 100, c);
+        assert!(limits.check(2, c).is_err());
+        // admins are exempt
+        propose(1, "open", 100, c);
+        propose(1, "open", 100, c);
+        assert!(limits.check(1, c).is_ok());
+    }
+
+    #[test]
+    fn max_submissions_within_window() {
+        let c = &mut test_connection();
+        setup(c);
+        let limits = SubmissionLimits {
+            max_submissions: Some(2),
+            ..SubmissionLimits::default()
+        };
+
+        propose(2, "discussed", 30, c);
+        propose(2, "open", 2, c);
+        assert!(limits.check(2, c).is_ok());
+        // withdrawn items still count as submissions
+        propose(2, "withdrawn", 1, c);
+        assert!(limits.check(2, c).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+use super::*;
+
+use crate::export::csv_escape;
+use anyhow::Error;
+use attendance::Attendance;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use item::{Forbidden, Item};
+use status::ItemStatus;
+use std::collections::HashMap;
+use user::User;
+
+/// Minutes and slides attached to an item after it has been discussed.
+#[derive(Serialize, Queryable, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct MeetingNotes {
+    pub item_id: i32,
+    pub minutes_markdown: String,
+    pub minutes_html: String,
+    /// one link per line
+    pub slides: String,
+    pub updated_by: Option<i32>,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(FromForm)]
+pub struct NotesData {
+    pub item: i32,
+    pub minutes: String,
+    pub slides: String,
+}
+
+#[derive(FromForm)]
+pub struct RatingData {
+    pub item: i32,
+    #[field(validate = range(1..=5))]
+    pub rating: i32,
+}
+
+/// Aggregated "was this worth it" ratings of a single item.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct RatingSummary {
+    pub item_id: i32,
+    pub count: usize,
+    pub average: f64,
+    /// number of ratings for 1 to 5
+    pub histogram: [usize; 5],
+}
+
+// items can be rated and annotated once the meeting took place
+fn was_discussed(item: &Item) -> bool {
+    let today: NaiveDate = Utc::now().naive_utc().into();
+    match item.status {
+        ItemStatus::Discussed | ItemStatus::FollowUp => true,
+        ItemStatus::Scheduled => item.discussed_on.is_some_and(|d| d <= today),
+        _ => false,
+    }
+}
+
+impl MeetingNotes {
+    pub async fn for_items(ids: Vec<i32>, conn: &DbConn) -> HashMap<i32, MeetingNotes> {
+        use self::schema::meeting_notes::dsl::{item_id as notes_item_id, meeting_notes};
+
+        conn.run(move |c| {
+            meeting_notes
+                .filter(notes_item_id.eq_any(ids))
+                .load::<MeetingNotes>(c)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|n| (n.item_id, n))
+                .collect()
+        })
+        .await
+    }
+
+    /// Admins and the proposer of an item can write its notes.
+    pub fn can_write(item: &Item, user: &User) -> bool {
+        user.is_admin || item.proposed_by == Some(user.id)
+    }
+
+    pub async fn save(
+        id: i32,
+        minutes_markdown: String,
+        minutes_html: String,
+        slides: String,
+        user: &User,
+        conn: &DbConn,
+    ) -> Result<()> {
+        use self::schema::meeting_notes::dsl as notes;
+
+        let item = Item::from_id(id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        if !MeetingNotes::can_write(&item, user) {
+            return Err(Forbidden("You are not allowed to write notes for this item.").into());
+        }
+        if !was_discussed(&item) {
+            return Err(Error::msg(
+                "Notes can only be added after the item was discussed.",
+            ));
+        }
+
+        let slides = slides
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>();
+        if let Some(link) = slides
+            .iter()
+            .find(|l| !l.starts_with("https://") && !l.starts_with("http://"))
+        {
+            return Err(Error::msg(format!("Not a http(s) link: {}", link)));
+        }
+        let slides = slides.join("\n");
+        let uid = user.id;
+        conn.run(move |c| {
+            let values = (
+                notes::item_id.eq(id),
+                notes::minutes_markdown.eq(minutes_markdown),
+                notes::minutes_html.eq(minutes_html),
+                notes::slides.eq(slides),
+                notes::updated_by.eq(uid),
+                notes::updated_at.eq(Utc::now().naive_utc()),
+            );
+            diesel::replace_into(notes::meeting_notes)
+                .values(values)
+                .execute(c)
+                .context("Failed to save notes in db.")?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+impl RatingSummary {
+    /// Ratings of the given items, or of all rated items if `ids` is empty.
+    pub async fn for_items(ids: Vec<i32>, conn: &DbConn) -> HashMap<i32, RatingSummary> {
+        use self::schema::ratings::dsl::{item_id as rating_item_id, rating, ratings};
+
+        conn.run(move |c| {
+            let mut query = ratings.select((rating_item_id, rating)).into_boxed();
+            if !ids.is_empty() {
+                query = query.filter(rating_item_id.eq_any(ids));
+            }
+            let mut summaries = HashMap::new();
+            for (id, value) in query.load::<(i32, i32)>(c).unwrap_or_default() {
+                let summary = summaries.entry(id).or_insert(RatingSummary {
+                    item_id: id,
+                    count: 0,
+                    average: 0.0,
+                    histogram: [0; 5],
+                });
+                summary.count += 1;
+                summary.histogram[(value.clamp(1, 5) - 1) as usize] += 1;
+            }
+            for summary in summaries.values_mut() {
+                let total: usize = (1..=5).zip(summary.histogram).map(|(v, n)| v * n).sum();
+                summary.average = total as f64 / summary.count as f64;
+            }
+            summaries
+        })
+        .await
+    }
+
+    /// Ratings of `uid` by item id.
+    pub async fn of_user(uid: i32, conn: &DbConn) -> HashMap<i32, i32> {
+        use self::schema::ratings::dsl::{item_id as rating_item_id, rating, ratings, user_id};
+
+        conn.run(move |c| {
+            ratings
+                .filter(user_id.eq(uid))
+                .select((rating_item_id, rating))
+                .load::<(i32, i32)>(c)
+                .unwrap_or_default()
+                .into_iter()
+                .collect()
+        })
+        .await
+    }
+
+    pub async fn rate(id: i32, uid: i32, value: i32, conn: &DbConn) -> Result<()> {
+        use self::schema::ratings::dsl as ratings;
+
+        if !(1..=5).contains(&value) {
+            return Err(Error::msg("Ratings must be between 1 and 5."));
+        }
+        let item = Item::from_id(id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        if !was_discussed(&item) {
+            return Err(Error::msg("Only discussed items can be rated."));
+        }
+
+        conn.run(move |c| {
+            if !Attendance::attended(id, uid, c)? {
+                return Err(Forbidden("Only attendees of the meeting can rate the item.").into());
+            }
+            diesel::replace_into(ratings::ratings)
+                .values((
+                    ratings::user_id.eq(uid),
+                    ratings::item_id.eq(id),
+                    ratings::rating.eq(value),
+                    ratings::rated_at.eq(Utc::now().naive_utc()),
+                ))
+                .execute(c)
+                .context("Failed to save rating in db.")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// CSV with one line per discussed item and its aggregated ratings.
+    pub fn to_csv(items: &[Item], summaries: &HashMap<i32, RatingSummary>) -> String {
+        let mut csv =
+            String::from("id,title,proposer,discussed_on,ratings,average,r1,r2,r3,r4,r5\n");
+        for item in items {
+            let (count, average, histogram) = match summaries.get(&item.id) {
+                Some(s) => (s.count, format!("{:.2}", s.average), s.histogram),
+                None => (0, String::new(), [0; 5]),
+            };
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                item.id,
+                csv_escape(&item.title),
+                csv_escape(item.proposer.as_deref().unwrap_or("")),
+                item.discussed_on.map(|d| d.to_string()).unwrap_or_default(),
+                count,
+                average,
+                histogram.map(|n| n.to_string()).join(",")
+            ));
+        }
+        csv
+    }
+}
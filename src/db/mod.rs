@@ -2,7 +2,26 @@ use anyhow::{Context, Result};
 use diesel::{self, prelude::*};
 use rocket::serde::{Deserialize, Serialize};
 
+pub mod attachment;
+pub mod attendance;
+pub mod bibliography;
+pub mod comment;
+pub mod endorsement;
+pub mod expiry;
+pub mod fairness;
 pub mod item;
+pub mod limits;
+pub mod meeting;
+pub mod moderation;
+pub mod notification;
+pub mod presenter;
+pub mod recommendation;
+pub mod revision;
+pub mod search;
+pub mod stats;
+pub mod status;
+pub mod tag;
+pub mod track;
 pub mod user;
 pub mod vote;
 
@@ -30,6 +49,25 @@ mod schema {
             html -> Text,
             markdown -> Text,
             discussed_on -> Nullable<Date>,
+            status -> Text,
+            proposed_by -> Nullable<Integer>,
+            created_at -> Timestamp,
+            authors -> Nullable<Text>,
+            year -> Nullable<Integer>,
+            venue -> Nullable<Text>,
+            doi -> Nullable<Text>,
+            arxiv_id -> Nullable<Text>,
+            url -> Nullable<Text>,
+        }
+    }
+
+    table! {
+        status_changes {
+            id -> Integer,
+            item_id -> Integer,
+            old_status -> Text,
+            new_status -> Text,
+            changed_at -> Timestamp,
         }
     }
 
@@ -38,16 +76,188 @@ mod schema {
             user_id -> Integer,
             item_id -> Integer,
             ordinal -> Integer,
+            voted_at -> Timestamp,
+        }
+    }
+
+    table! {
+        revisions {
+            id -> Integer,
+            item_id -> Integer,
+            title -> Text,
+            markdown -> Text,
+            html -> Text,
+            edited_by -> Nullable<Integer>,
+            edited_at -> Timestamp,
+            authors -> Nullable<Text>,
+            year -> Nullable<Integer>,
+            venue -> Nullable<Text>,
+            doi -> Nullable<Text>,
+            arxiv_id -> Nullable<Text>,
+            url -> Nullable<Text>,
+        }
+    }
+
+    table! {
+        comments {
+            id -> Integer,
+            item_id -> Integer,
+            user_id -> Integer,
+            markdown -> Text,
+            html -> Text,
+            created_at -> Timestamp,
+            edited_at -> Nullable<Timestamp>,
+        }
+    }
+
+    table! {
+        meeting_notes (item_id) {
+            item_id -> Integer,
+            minutes_markdown -> Text,
+            minutes_html -> Text,
+            slides -> Text,
+            updated_by -> Nullable<Integer>,
+            updated_at -> Timestamp,
+        }
+    }
+
+    table! {
+        attachments {
+            id -> Integer,
+            item_id -> Integer,
+            filename -> Text,
+            content_type -> Text,
+            size -> BigInt,
+            uploaded_by -> Nullable<Integer>,
+            uploaded_at -> Timestamp,
+        }
+    }
+
+    table! {
+        notifications {
+            id -> Integer,
+            user_id -> Integer,
+            item_id -> Nullable<Integer>,
+            message -> Text,
+            created_at -> Timestamp,
+            is_read -> Bool,
+        }
+    }
+
+    table! {
+        ratings (user_id, item_id) {
+            user_id -> Integer,
+            item_id -> Integer,
+            rating -> Integer,
+            rated_at -> Timestamp,
+        }
+    }
+
+    table! {
+        endorsements (user_id, item_id) {
+            user_id -> Integer,
+            item_id -> Integer,
+            endorsed_at -> Timestamp,
+        }
+    }
+
+    table! {
+        volunteers (user_id, item_id) {
+            user_id -> Integer,
+            item_id -> Integer,
+            volunteered_at -> Timestamp,
+        }
+    }
+
+    table! {
+        presenters (item_id) {
+            item_id -> Integer,
+            user_id -> Integer,
+            assigned_at -> Timestamp,
+        }
+    }
+
+    table! {
+        attendance (item_id, user_id) {
+            item_id -> Integer,
+            user_id -> Integer,
+            checked_in_at -> Timestamp,
+            checked_in_by -> Nullable<Integer>,
+        }
+    }
+
+    table! {
+        ballots {
+            id -> Integer,
+            user_id -> Integer,
+            submitted_at -> Timestamp,
+        }
+    }
+
+    table! {
+        tags {
+            id -> Integer,
+            name -> Text,
+        }
+    }
+
+    table! {
+        item_tags (item_id, tag_id) {
+            item_id -> Integer,
+            tag_id -> Integer,
         }
     }
 
     joinable!(votes -> items (item_id));
     joinable!(votes -> users (user_id));
-    allow_tables_to_appear_in_same_query!(users, items, votes);
+    joinable!(items -> users (proposed_by));
+    joinable!(status_changes -> items (item_id));
+    joinable!(revisions -> items (item_id));
+    joinable!(revisions -> users (edited_by));
+    joinable!(comments -> items (item_id));
+    joinable!(comments -> users (user_id));
+    joinable!(meeting_notes -> items (item_id));
+    joinable!(attachments -> items (item_id));
+    joinable!(attachments -> users (uploaded_by));
+    joinable!(notifications -> items (item_id));
+    joinable!(notifications -> users (user_id));
+    joinable!(ratings -> items (item_id));
+    joinable!(ratings -> users (user_id));
+    joinable!(endorsements -> items (item_id));
+    joinable!(endorsements -> users (user_id));
+    joinable!(volunteers -> items (item_id));
+    joinable!(volunteers -> users (user_id));
+    joinable!(presenters -> items (item_id));
+    joinable!(presenters -> users (user_id));
+    joinable!(attendance -> items (item_id));
+    joinable!(attendance -> users (user_id));
+    joinable!(ballots -> users (user_id));
+    joinable!(item_tags -> items (item_id));
+    joinable!(item_tags -> tags (tag_id));
+    allow_tables_to_appear_in_same_query!(
+        users,
+        items,
+        votes,
+        status_changes,
+        revisions,
+        comments,
+        meeting_notes,
+        attachments,
+        notifications,
+        ratings,
+        endorsements,
+        volunteers,
+        presenters,
+        attendance,
+        ballots,
+        tags,
+        item_tags
+    );
 }
 
 use self::schema::items::dsl::{
-    discussed_on as item_discussed_on, id as item_id, items as all_items,
+    created_at as item_created_at, discussed_on as item_discussed_on, id as item_id,
+    items as all_items, proposed_by as item_proposed_by, status as item_status,
 };
 use self::schema::users::dsl::{
     id as user_id, is_admin as user_admin, is_approved as user_approved, password as user_password,
@@ -56,3 +266,14 @@ use self::schema::users::dsl::{
 use self::schema::votes::dsl::{
     item_id as vote_item_id, ordinal, user_id as vote_user_id, votes as all_votes,
 };
+
+// empty in-memory database with the full schema for unit tests
+#[cfg(test)]
+pub(crate) fn test_connection() -> SqliteConnection {
+    use diesel::connection::SimpleConnection;
+
+    let mut c = SqliteConnection::establish(":memory:").expect("in-memory database");
+    c.batch_execute(include_str!("../../schema.sql"))
+        .expect("schema.sql");
+    c
+}
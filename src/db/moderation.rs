@@ -0,0 +1,88 @@
+use super::*;
+
+use anyhow::Error;
+use item::Item;
+use notification::Notification;
+use status::{ItemStatus, StatusChange};
+use user::User;
+
+/// Review of new items by admins, configured in `Rocket.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ModerationConfig {
+    /// new items of other users than admins wait for approval
+    pub moderate_items: bool,
+}
+
+impl ModerationConfig {
+    /// Status of a new item proposed by `user`.
+    pub fn initial_status(&self, user: &User) -> ItemStatus {
+        if self.moderate_items && !user.is_admin {
+            ItemStatus::Pending
+        } else {
+            ItemStatus::Open
+        }
+    }
+}
+
+#[derive(FromForm)]
+pub struct ModerateData {
+    pub id: i32,
+    pub approve: bool,
+    /// told the proposer if the item is rejected
+    pub reason: Option<String>,
+}
+
+impl Item {
+    /// Items waiting for moderation, oldest first.
+    pub async fn get_pending(conn: &DbConn) -> Vec<Item> {
+        conn.run(move |c| {
+            Item::query()
+                .filter(item_status.eq(ItemStatus::Pending))
+                .order(item_created_at.asc())
+                .load::<Item>(c)
+                .unwrap_or_default()
+        })
+        .await
+    }
+
+    /// Opens or rejects a pending item and notifies its proposer of the decision.
+    pub async fn moderate(
+        id: i32,
+        approve: bool,
+        reason: Option<String>,
+        conn: &DbConn,
+    ) -> Result<()> {
+        let item = Item::from_id(id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        if item.status != ItemStatus::Pending {
+            return Err(Error::msg(format!("Item {} is not pending.", id)));
+        }
+
+        conn.run(move |c| {
+            c.transaction(|c| {
+                let message = if approve {
+                    StatusChange::apply(id, ItemStatus::Open, c)?;
+                    format!(
+                        "Your item \"{}\" was approved and is on the ballot now.",
+                        item.title
+                    )
+                } else {
+                    StatusChange::apply(id, ItemStatus::Rejected, c)?;
+                    match reason.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+                        Some(reason) => {
+                            format!("Your item \"{}\" was rejected: {}", item.title, reason)
+                        }
+                        None => format!("Your item \"{}\" was rejected.", item.title),
+                    }
+                };
+                if let Some(uid) = item.proposed_by {
+                    Notification::send(uid, Some(id), message, c)?;
+                }
+                Ok(())
+            })
+        })
+        .await
+    }
+}
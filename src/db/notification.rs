@@ -0,0 +1,98 @@
+use super::*;
+
+use chrono::{NaiveDateTime, Utc};
+
+/// Message to a user about one of their items, shown on `/notifications`.
+#[derive(Serialize, Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = self::schema::notifications)]
+#[serde(crate = "rocket::serde")]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub item_id: Option<i32>,
+    pub message: String,
+    pub created_at: NaiveDateTime,
+    pub is_read: bool,
+}
+
+impl Notification {
+    /// Notifications of user `uid`, newest first.
+    pub async fn for_user(uid: i32, conn: &DbConn) -> Vec<Notification> {
+        use self::schema::notifications::dsl::{created_at, notifications, user_id};
+
+        conn.run(move |c| {
+            notifications
+                .filter(user_id.eq(uid))
+                .order(created_at.desc())
+                .select(Notification::as_select())
+                .load::<Notification>(c)
+                .unwrap_or_default()
+        })
+        .await
+    }
+
+    pub async fn unread_count(uid: i32, conn: &DbConn) -> i64 {
+        use self::schema::notifications::dsl::{is_read, notifications, user_id};
+
+        conn.run(move |c| {
+            notifications
+                .filter(user_id.eq(uid))
+                .filter(is_read.eq(false))
+                .count()
+                .get_result::<i64>(c)
+                .unwrap_or_default()
+        })
+        .await
+    }
+
+    pub async fn mark_read(uid: i32, conn: &DbConn) -> Result<()> {
+        use self::schema::notifications::dsl::{is_read, notifications, user_id};
+
+        conn.run(move |c| {
+            diesel::update(notifications.filter(user_id.eq(uid)))
+                .set(is_read.eq(true))
+                .execute(c)
+                .context("Failed to update notifications in db.")?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Notifies user `uid`, usually inside the transaction causing the notification.
+    pub(crate) fn send(
+        uid: i32,
+        item: Option<i32>,
+        message: String,
+        c: &mut SqliteConnection,
+    ) -> Result<()> {
+        use self::schema::notifications::dsl as notifications;
+
+        diesel::insert_into(notifications::notifications)
+            .values((
+                notifications::user_id.eq(uid),
+                notifications::item_id.eq(item),
+                notifications::message.eq(message),
+                notifications::created_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(c)
+            .context("Failed inserting notification into db.")?;
+        Ok(())
+    }
+
+    /// Notifies all admins, see `send`.
+    pub(crate) fn send_to_admins(
+        item: Option<i32>,
+        message: String,
+        c: &mut SqliteConnection,
+    ) -> Result<()> {
+        let admins = all_users
+            .filter(user_admin.eq(true))
+            .select(user_id)
+            .load::<i32>(c)
+            .context("Failed to read users from db.")?;
+        for uid in admins {
+            Notification::send(uid, item, message.clone(), c)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use super::*;
+
+use self::schema::presenters::dsl::{
+    assigned_at, item_id as presenter_item_id, presenters, user_id as presenter_user_id,
+};
+use self::schema::volunteers::dsl::{
+    item_id as volunteer_item_id, user_id as volunteer_user_id, volunteered_at, volunteers,
+};
+use anyhow::Error;
+use chrono::{NaiveDate, Utc};
+use item::Item;
+use status::ItemStatus;
+use std::collections::HashMap;
+
+/// User willing to present an item.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Volunteer {
+    pub item_id: i32,
+    pub user_id: i32,
+    pub username: String,
+}
+
+#[derive(FromForm)]
+pub struct VolunteerData {
+    pub item: i32,
+    /// `false` withdraws the offer
+    pub volunteer: bool,
+}
+
+#[derive(FromForm)]
+pub struct AssignPresenterData {
+    pub item: i32,
+    /// `None` removes the presenter
+    pub user: Option<i32>,
+}
+
+/// How often a user presented, see `Presenter::report`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct PresenterStats {
+    pub user_id: i32,
+    pub username: String,
+    /// presentations on or before today
+    pub presented: usize,
+    /// presentations of scheduled items after today
+    pub upcoming: usize,
+    pub last_presented: Option<NaiveDate>,
+    /// items the user currently volunteers for
+    pub volunteered: usize,
+}
+
+// users can offer to present items which are still to be discussed
+fn can_volunteer(status: ItemStatus) -> bool {
+    matches!(status, ItemStatus::Open | ItemStatus::Scheduled)
+}
+
+impl Volunteer {
+    /// Volunteers of the given items by item id, in the order they volunteered.
+    pub async fn for_items(ids: Vec<i32>, conn: &DbConn) -> HashMap<i32, Vec<Volunteer>> {
+        conn.run(move |c| {
+            volunteers
+                .inner_join(all_users)
+                .filter(volunteer_item_id.eq_any(ids))
+                .order(volunteered_at.asc())
+                .select((volunteer_item_id, volunteer_user_id, user_username))
+                .load::<(i32, i32, String)>(c)
+                .unwrap_or_default()
+                .into_iter()
+                .fold(HashMap::new(), |mut map, (item, uid, username)| {
+                    map.entry(item).or_insert_with(Vec::new).push(Volunteer {
+                        item_id: item,
+                        user_id: uid,
+                        username,
+                    });
+                    map
+                })
+        })
+        .await
+    }
+
+    /// Offers `uid` as presenter of item `id` or withdraws the offer.
+    pub async fn set(id: i32, uid: i32, volunteer: bool, conn: &DbConn) -> Result<()> {
+        if volunteer {
+            let item = Item::from_id(id, conn)
+                .await
+                .ok_or(Error::msg("Could not retrieve item from database"))?;
+            if !can_volunteer(item.status) {
+                return Err(Error::msg(
+                    "Only open or scheduled items can be volunteered for.",
+                ));
+            }
+        }
+
+        conn.run(move |c| {
+            if volunteer {
+                diesel::insert_or_ignore_into(volunteers)
+                    .values((
+                        volunteer_user_id.eq(uid),
+                        volunteer_item_id.eq(id),
+                        volunteered_at.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(c)
+                    .context("Failed to save volunteer in db.")?;
+            } else {
+                diesel::delete(
+                    volunteers
+                        .filter(volunteer_user_id.eq(uid))
+                        .filter(volunteer_item_id.eq(id)),
+                )
+                .execute(c)
+                .context("Failed to delete volunteer from db.")?;
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+pub struct Presenter;
+
+impl Presenter {
+    /// Usernames of the presenters of the given items by item id.
+    pub async fn for_items(ids: Vec<i32>, conn: &DbConn) -> HashMap<i32, String> {
+        conn.run(move |c| {
+            presenters
+                .inner_join(all_users)
+                .filter(presenter_item_id.eq_any(ids))
+                .select((presenter_item_id, user_username))
+                .load::<(i32, String)>(c)
+                .unwrap_or_default()
+                .into_iter()
+                .collect()
+        })
+        .await
+    }
+
+    pub async fn for_item(id: i32, conn: &DbConn) -> Option<String> {
+        Presenter::for_items(vec![id], conn).await.remove(&id)
+    }
+
+    /// Assigns one of the volunteers as presenter of a scheduled item, `None` removes the presenter.
+    pub async fn assign(id: i32, uid: Option<i32>, conn: &DbConn) -> Result<()> {
+        let item = Item::from_id(id, conn)
+            .await
+            .ok_or(Error::msg("Could not retrieve item from database"))?;
+        if uid.is_some() && !item.status.has_date() {
+            return Err(Error::msg("Only scheduled items can get a presenter."));
+        }
+
+        conn.run(move |c| {
+            c.transaction(|c| {
+                let Some(uid) = uid else {
+                    return Presenter::remove(id, c);
+                };
+                let volunteered = volunteers
+                    .filter(volunteer_item_id.eq(id))
+                    .filter(volunteer_user_id.eq(uid))
+                    .count()
+                    .get_result::<i64>(c)
+                    .context("Failed to read volunteers from db.")?;
+                if volunteered == 0 {
+                    return Err(Error::msg("The presenter has to volunteer for the item."));
+                }
+                diesel::replace_into(presenters)
+                    .values((
+                        presenter_item_id.eq(id),
+                        presenter_user_id.eq(uid),
+                        assigned_at.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(c)
+                    .context("Failed to save presenter in db.")?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Presentations of all approved users, the users who presented least (and longest ago) first.
+    pub async fn report(conn: &DbConn) -> Result<Vec<PresenterStats>> {
+        conn.run(move |c| {
+            let today: NaiveDate = Utc::now().naive_utc().into();
+            let mut stats: HashMap<i32, PresenterStats> = all_users
+                .filter(user_approved.eq(true))
+                .select((user_id, user_username))
+                .load::<(i32, String)>(c)
+                .context("Failed to read users from db.")?
+                .into_iter()
+                .map(|(id, username)| {
+                    let stats = PresenterStats {
+                        user_id: id,
+                        username,
+                        presented: 0,
+                        upcoming: 0,
+                        last_presented: None,
+                        volunteered: 0,
+                    };
+                    (id, stats)
+                })
+                .collect();
+
+            let presentations = presenters
+                .inner_join(all_items)
+                .select((presenter_user_id, item_status, item_discussed_on))
+                .load::<(i32, ItemStatus, Option<NaiveDate>)>(c)
+                .context("Failed to read presenters from db.")?;
+            for (uid, status, date) in presentations {
+                let Some(s) = stats.get_mut(&uid) else {
+                    continue;
+                };
+                // presenters of items without a meeting date are left over and do not count
+                match date.filter(|_| status.has_date()) {
+                    Some(d) if d <= today => {
+                        s.presented += 1;
+                        s.last_presented = s.last_presented.max(Some(d));
+                    }
+                    Some(_) if status == ItemStatus::Scheduled => s.upcoming += 1,
+                    _ => {}
+                }
+            }
+
+            let offers = volunteers
+                .inner_join(all_items)
+                .filter(item_status.eq_any([ItemStatus::Open, ItemStatus::Scheduled]))
+                .select(volunteer_user_id)
+                .load::<i32>(c)
+                .context("Failed to read volunteers from db.")?;
+            for uid in offers {
+                if let Some(s) = stats.get_mut(&uid) {
+                    s.volunteered += 1;
+                }
+            }
+
+            let mut stats: Vec<PresenterStats> = stats.into_values().collect();
+            stats.sort_by(|a, b| {
+                (a.presented + a.upcoming, a.last_presented, &a.username).cmp(&(
+                    b.presented + b.upcoming,
+                    b.last_presented,
+                    &b.username,
+                ))
+            });
+            Ok(stats)
+        })
+        .await
+    }
+
+    /// Removes the presenter of item `id`, used when the item loses its meeting date.
+    pub(crate) fn remove(id: i32, c: &mut SqliteConnection) -> Result<()> {
+        diesel::delete(presenters.filter(presenter_item_id.eq(id)))
+            .execute(c)
+            .context("Failed to remove presenter from db.")?;
+        Ok(())
+    }
+
+    /// Moves volunteers and the presenter of item `drop` to `keep`, used when merging items.
+    ///
+    /// The presenter of `keep` stays if both items have one.
+    pub(crate) fn merge(keep: i32, drop: i32, c: &mut SqliteConnection) -> Result<()> {
+        let volunteered_keep = volunteers
+            .filter(volunteer_item_id.eq(keep))
+            .select(volunteer_user_id)
+            .load::<i32>(c)
+            .context("Failed to move volunteers.")?;
+        diesel::update(
+            volunteers
+                .filter(volunteer_item_id.eq(drop))
+                .filter(volunteer_user_id.ne_all(volunteered_keep)),
+        )
+        .set(volunteer_item_id.eq(keep))
+        .execute(c)
+        .context("Failed to move volunteers.")?;
+
+        let keep_has_presenter = presenters
+            .filter(presenter_item_id.eq(keep))
+            .count()
+            .get_result::<i64>(c)
+            .context("Failed to move presenter.")?
+            > 0;
+        if !keep_has_presenter {
+            diesel::update(presenters.filter(presenter_item_id.eq(drop)))
+                .set(presenter_item_id.eq(keep))
+                .execute(c)
+                .context("Failed to move presenter.")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use super::*;
+
+use status::ItemStatus;
+use std::collections::HashMap;
+
+/// Number of items recommended on the ballot.
+pub const RECOMMENDATIONS: usize = 5;
+/// Number of most similar voters whose ballots are used for the recommendations.
+const NEIGHBOURS: usize = 10;
+/// Number of items two voters must both have ranked to compare their ballots.
+const MIN_OVERLAP: usize = 2;
+
+/// An open item the user did not rank yet, see `Recommendation::for_user`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Recommendation {
+    pub item_id: i32,
+    /// predicted preference between 0 (ranked last) and 1 (ranked first)
+    pub score: f64,
+    /// similar voters who ranked the item
+    pub voters: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct Recommendations {
+    /// `false` if the user has no similar voters yet and the items are just popular
+    pub personalized: bool,
+    pub items: Vec<Recommendation>,
+}
+
+// scores of the ranked open items, from 1 for the first rank down to 1/n for the last one
+type Scores = HashMap<i32, f64>;
+
+// Spearman's rank correlation of the items ranked by both voters, `None` if they share too few
+fn correlation(a: &Scores, b: &Scores) -> Option<f64> {
+    let shared: Vec<i32> = a
+        .keys()
+        .filter(|item| b.contains_key(item))
+        .copied()
+        .collect();
+    if shared.len() < MIN_OVERLAP {
+        return None;
+    }
+    // ranks among the shared items, the best one first
+    let ranks = |s: &Scores| -> HashMap<i32, f64> {
+        let mut items = shared.clone();
+        items.sort_by(|x, y| s[y].total_cmp(&s[x]));
+        items
+            .into_iter()
+            .enumerate()
+            .map(|(rank, item)| (item, rank as f64))
+            .collect()
+    };
+    let (ranks_a, ranks_b) = (ranks(a), ranks(b));
+    let squared: f64 = shared
+        .iter()
+        .map(|item| (ranks_a[item] - ranks_b[item]).powi(2))
+        .sum();
+    let n = shared.len() as f64;
+    Some(1.0 - 6.0 * squared / (n * (n * n - 1.0)))
+}
+
+impl Recommendation {
+    /// Recommends items among `candidates` which `uid` did not rank, best first.
+    ///
+    /// Users who ranked at least `MIN_OVERLAP` of the same open items in a similar order
+    /// (positive rank correlation) are neighbours, the items they ranked highly are recommended.
+    /// Without any neighbour, e.g. for new members with an empty ballot, all ballots count
+    /// equally.
+    pub async fn for_user(uid: i32, candidates: Vec<i32>, conn: &DbConn) -> Recommendations {
+        conn.run(move |c| {
+            let rows = all_votes
+                .inner_join(all_items)
+                .filter(item_status.eq(ItemStatus::Open))
+                .order((vote_user_id.asc(), ordinal.asc()))
+                .select((vote_user_id, vote_item_id))
+                .load::<(i32, i32)>(c)
+                .unwrap_or_default();
+
+            let mut ballots: HashMap<i32, Vec<i32>> = HashMap::new();
+            for (voter, item) in rows {
+                ballots.entry(voter).or_default().push(item);
+            }
+            let scores: HashMap<i32, Scores> = ballots
+                .into_iter()
+                .map(|(voter, ballot)| {
+                    let n = ballot.len() as f64;
+                    let scores = ballot
+                        .into_iter()
+                        .enumerate()
+                        .map(|(rank, item)| (item, (n - rank as f64) / n))
+                        .collect();
+                    (voter, scores)
+                })
+                .collect();
+
+            let own = scores.get(&uid).cloned().unwrap_or_default();
+            let mut neighbours: Vec<(f64, &Scores)> = scores
+                .iter()
+                .filter(|(voter, _)| **voter != uid)
+                .filter_map(|(_, s)| Some((correlation(&own, s)?, s)))
+                .filter(|(similarity, _)| *similarity > 0.0)
+                .collect();
+            let personalized = !neighbours.is_empty();
+            if personalized {
+                neighbours.sort_by(|a, b| b.0.total_cmp(&a.0));
+                neighbours.truncate(NEIGHBOURS);
+            } else {
+                neighbours = scores
+                    .iter()
+                    .filter(|(voter, _)| **voter != uid)
+                    .map(|(_, s)| (1.0, s))
+                    .collect();
+            }
+
+            // weighted average over all neighbours, not ranking an item counts as 0
+            let total: f64 = neighbours.iter().map(|(similarity, _)| similarity).sum();
+            let mut items: Vec<Recommendation> = candidates
+                .into_iter()
+                .filter(|item| !own.contains_key(item))
+                .filter_map(|item| {
+                    let ranked: Vec<(f64, f64)> = neighbours
+                        .iter()
+                        .filter_map(|(similarity, s)| s.get(&item).map(|v| (*similarity, *v)))
+                        .collect();
+                    if ranked.is_empty() {
+                        return None;
+                    }
+                    let score = ranked.iter().map(|(sim, v)| sim * v).sum::<f64>() / total;
+                    Some(Recommendation {
+                        item_id: item,
+                        score,
+                        voters: ranked.len(),
+                    })
+                })
+                .collect();
+            items.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.item_id.cmp(&b.item_id)));
+            items.truncate(RECOMMENDATIONS);
+
+            Recommendations {
+                personalized,
+                items,
+            }
+        })
+        .await
+    }
+}
@@ -0,0 +1,176 @@
+use super::*;
+
+use bibliography::Bibliography;
+use chrono::{NaiveDateTime, Utc};
+use similar::{ChangeTag, TextDiff};
+
+/// Snapshot of the editable content of an item, stored on every edit.
+#[derive(Serialize, Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = self::schema::revisions)]
+#[serde(crate = "rocket::serde")]
+pub struct Revision {
+    pub id: i32,
+    pub item_id: i32,
+    pub title: String,
+    pub markdown: String,
+    pub html: String,
+    pub edited_by: Option<i32>,
+    pub edited_at: NaiveDateTime,
+    /// bibliographic fields of the item, see `Revision::bib`
+    pub authors: Option<String>,
+    pub year: Option<i32>,
+    pub venue: Option<String>,
+    pub doi: Option<String>,
+    pub arxiv_id: Option<String>,
+    pub url: Option<String>,
+    /// username of the editor, taken from the joined users table
+    #[diesel(select_expression = self::schema::users::username.nullable())]
+    #[diesel(select_expression_type = diesel::dsl::Nullable<self::schema::users::username>)]
+    pub editor: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = self::schema::revisions)]
+struct NewRevision {
+    item_id: i32,
+    title: String,
+    markdown: String,
+    html: String,
+    edited_by: Option<i32>,
+    edited_at: NaiveDateTime,
+    authors: Option<String>,
+    year: Option<i32>,
+    venue: Option<String>,
+    doi: Option<String>,
+    arxiv_id: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct DiffLine {
+    pub tag: &'static str,
+    pub text: String,
+}
+
+#[derive(FromForm)]
+pub struct RollbackData {
+    pub id: i32,
+    pub revision: i32,
+}
+
+impl Revision {
+    pub async fn for_item(id: i32, conn: &DbConn) -> Vec<Revision> {
+        use self::schema::revisions::dsl::{item_id as revision_item_id, revisions};
+
+        conn.run(move |c| {
+            revisions
+                .left_join(all_users)
+                .filter(revision_item_id.eq(id))
+                .order(self::schema::revisions::id.asc())
+                .select(Revision::as_select())
+                .load::<Revision>(c)
+                .unwrap_or_default()
+        })
+        .await
+    }
+
+    /// Stores the current content of item `id` as a new revision.
+    pub(crate) fn record(
+        id: i32,
+        editor: Option<i32>,
+        edited_at: NaiveDateTime,
+        c: &mut SqliteConnection,
+    ) -> Result<()> {
+        use self::schema::items::dsl::{html, markdown, title};
+
+        let (item_title, item_markdown, item_html, bib) = all_items
+            .filter(item_id.eq(id))
+            .select((title, markdown, html, Bibliography::as_select()))
+            .get_result::<(String, String, String, Bibliography)>(c)
+            .context("Item not found in db.")?;
+
+        diesel::insert_into(self::schema::revisions::table)
+            .values(NewRevision {
+                item_id: id,
+                title: item_title,
+                markdown: item_markdown,
+                html: item_html,
+                edited_by: editor,
+                edited_at,
+                authors: bib.authors,
+                year: bib.year,
+                venue: bib.venue,
+                doi: bib.doi,
+                arxiv_id: bib.arxiv_id,
+                url: bib.url,
+            })
+            .execute(c)
+            .context("Failed to store revision in db.")?;
+        Ok(())
+    }
+
+    /// Records the content of an item before its first edit.
+    ///
+    /// Items created before revisions were introduced have no revision yet, their original
+    /// content is attributed to the proposer.
+    pub(crate) fn ensure_initial(id: i32, c: &mut SqliteConnection) -> Result<()> {
+        use self::schema::revisions::dsl::{item_id as revision_item_id, revisions};
+
+        let count = revisions
+            .filter(revision_item_id.eq(id))
+            .count()
+            .get_result::<i64>(c)
+            .context("Failed to read revisions from db.")?;
+        if count > 0 {
+            return Ok(());
+        }
+
+        let (proposer, created) = all_items
+            .filter(item_id.eq(id))
+            .select((item_proposed_by, item_created_at))
+            .get_result::<(Option<i32>, NaiveDateTime)>(c)
+            .context("Item not found in db.")?;
+        Revision::record(id, proposer, created, c)
+    }
+
+    pub(crate) fn record_now(id: i32, editor: i32, c: &mut SqliteConnection) -> Result<()> {
+        Revision::record(id, Some(editor), Utc::now().naive_utc(), c)
+    }
+
+    pub fn bib(&self) -> Bibliography {
+        Bibliography {
+            authors: self.authors.clone(),
+            year: self.year,
+            venue: self.venue.clone(),
+            doi: self.doi.clone(),
+            arxiv_id: self.arxiv_id.clone(),
+            url: self.url.clone(),
+        }
+    }
+
+    fn text(&self) -> String {
+        format!(
+            "{}\n-------\n{}",
+            self.title,
+            self.bib().compose(&self.markdown)
+        )
+    }
+
+    /// Line based diff of the markdown from `self` to `other`.
+    pub fn diff(&self, other: &Revision) -> Vec<DiffLine> {
+        let old = self.text();
+        let new = other.text();
+        TextDiff::from_lines(&old, &new)
+            .iter_all_changes()
+            .map(|change| DiffLine {
+                tag: match change.tag() {
+                    ChangeTag::Equal => "equal",
+                    ChangeTag::Delete => "delete",
+                    ChangeTag::Insert => "insert",
+                },
+                text: change.value().trim_end_matches('\n').to_string(),
+            })
+            .collect()
+    }
+}
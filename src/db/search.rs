@@ -0,0 +1,118 @@
+use super::*;
+
+use crate::markdown::escape_html;
+use diesel::sql_types::{Double, Integer, Text};
+use item::{Item, ItemFilter};
+use status::ItemStatus;
+use std::collections::HashMap;
+
+/// Parameters of `/search` and `/search.json`, dates are given as `YYYY-MM-DD`.
+#[derive(FromForm, Serialize, Default, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct SearchQuery {
+    #[field(default = "")]
+    pub q: String,
+    /// any status if empty
+    pub status: Vec<ItemStatus>,
+    /// only items discussed on or after this date
+    pub from: Option<String>,
+    /// only items discussed on or before this date
+    pub to: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Item matching a full-text search, see `Item::search`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub item: Item,
+    /// bm25 score of the match, lower is better
+    pub rank: f64,
+    /// html escaped excerpt with the matched terms wrapped in `<mark>`
+    pub snippet: String,
+}
+
+#[derive(QueryableByName)]
+struct Match {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Double)]
+    rank: f64,
+    #[diesel(sql_type = Text)]
+    snippet: String,
+}
+
+// markers around matched terms which cannot be part of the escaped snippet
+const MARK_START: char = '\u{2}';
+const MARK_END: char = '\u{3}';
+
+// every word of the query has to match, either exactly (with stemming) or as a prefix
+fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<_> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| format!("(\"{0}\" OR \"{0}\"*)", t))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" AND "))
+    }
+}
+
+fn highlight(snippet: &str) -> String {
+    escape_html(snippet)
+        .replace(MARK_START, "<mark>")
+        .replace(MARK_END, "</mark>")
+}
+
+impl Item {
+    /// Items matching all words of `query` in their title, notes, authors or venue, best first.
+    ///
+    /// Matches in the title weigh most, followed by the authors, the venue and the notes.
+    pub async fn search(
+        query: &str,
+        filter: ItemFilter,
+        limit: usize,
+        conn: &DbConn,
+    ) -> Result<Vec<SearchHit>> {
+        let Some(expression) = match_expression(query) else {
+            return Ok(Vec::new());
+        };
+        let matches = conn
+            .run(move |c| {
+                diesel::sql_query(format!(
+                    "SELECT rowid AS id, bm25(items_fts, 10.0, 1.0, 5.0, 2.0) AS rank, \
+                     snippet(items_fts, -1, '{}', '{}', '…', 16) AS snippet \
+                     FROM items_fts WHERE items_fts MATCH ? ORDER BY rank",
+                    MARK_START, MARK_END
+                ))
+                .bind::<Text, _>(expression)
+                .load::<Match>(c)
+                .context("Failed to search items.")
+            })
+            .await?;
+        if matches.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ids = matches.iter().map(|m| m.id).collect();
+        let mut items: HashMap<i32, Item> = Item::from_ids(ids, filter, conn)
+            .await?
+            .into_iter()
+            .map(|item| (item.id, item))
+            .collect();
+        Ok(matches
+            .into_iter()
+            .filter_map(|m| {
+                Some(SearchHit {
+                    item: items.remove(&m.id)?,
+                    rank: m.rank,
+                    snippet: highlight(&m.snippet),
+                })
+            })
+            .take(limit)
+            .collect())
+    }
+}
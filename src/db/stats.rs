@@ -0,0 +1,197 @@
+use super::*;
+
+use self::schema::ballots::dsl::{ballots, submitted_at, user_id as ballot_user_id};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use status::ItemStatus;
+use std::collections::{HashMap, HashSet};
+
+/// Number of weeks shown in the weekly charts, including the current week.
+pub const WEEKS: usize = 26;
+/// Number of months shown in the monthly charts, including the current month.
+pub const MONTHS: usize = 12;
+/// Number of users listed as top proposers.
+pub const TOP_PROPOSERS: usize = 10;
+
+/// Count of one week (labeled by its Monday) or one month (labeled `YYYY-MM`).
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Period {
+    pub label: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Proposer {
+    pub username: String,
+    pub proposed: usize,
+    pub discussed: usize,
+}
+
+/// Participation numbers for the admin dashboard.
+///
+/// Ballots are counted from the log written by `Vote::save_ballot`, so submissions before the
+/// log existed are missing. Pending and rejected items do not count as proposed.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct ActivityStats {
+    pub ballots_per_week: Vec<Period>,
+    /// users who submitted at least one ballot in the week
+    pub voters_per_week: Vec<Period>,
+    /// users whose current ballot ranks at least one open item
+    pub active_voters: usize,
+    pub proposed_per_month: Vec<Period>,
+    pub discussed_per_month: Vec<Period>,
+    /// average over all discussed items with a known creation time before their meeting, `None`
+    /// if there are none
+    pub avg_days_to_discussion: Option<f64>,
+    pub top_proposers: Vec<Proposer>,
+}
+
+fn week_of(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn month_of(date: NaiveDate) -> String {
+    date.format("%Y-%m").to_string()
+}
+
+// the last `WEEKS` Mondays, oldest first
+fn weeks(today: NaiveDate) -> Vec<NaiveDate> {
+    let current = week_of(today);
+    (0..WEEKS)
+        .rev()
+        .map(|i| current - Duration::weeks(i as i64))
+        .collect()
+}
+
+// the last `MONTHS` months, oldest first
+fn months(today: NaiveDate) -> Vec<String> {
+    let current = today.year() * 12 + today.month0() as i32;
+    (0..MONTHS as i32)
+        .rev()
+        .map(|i| {
+            let month = current - i;
+            format!(
+                "{:04}-{:02}",
+                month.div_euclid(12),
+                month.rem_euclid(12) + 1
+            )
+        })
+        .collect()
+}
+
+fn periods<K: std::hash::Hash + Eq + ToString>(
+    keys: Vec<K>,
+    counts: &HashMap<K, usize>,
+) -> Vec<Period> {
+    keys.into_iter()
+        .map(|k| Period {
+            count: counts.get(&k).copied().unwrap_or(0),
+            label: k.to_string(),
+        })
+        .collect()
+}
+
+impl ActivityStats {
+    pub async fn compute(conn: &DbConn) -> Result<ActivityStats> {
+        conn.run(move |c| {
+            let today: NaiveDate = Utc::now().naive_utc().into();
+            let weeks = weeks(today);
+            let months = months(today);
+            let since: NaiveDateTime = weeks[0].into();
+
+            let submissions = ballots
+                .filter(submitted_at.ge(since))
+                .select((ballot_user_id, submitted_at))
+                .load::<(i32, NaiveDateTime)>(c)
+                .context("Failed to read ballots from db.")?;
+            let mut ballot_counts = HashMap::new();
+            let mut voters: HashMap<NaiveDate, HashSet<i32>> = HashMap::new();
+            for (uid, at) in submissions {
+                let week = week_of(at.date());
+                *ballot_counts.entry(week).or_insert(0) += 1;
+                voters.entry(week).or_default().insert(uid);
+            }
+            let voter_counts = voters.into_iter().map(|(w, v)| (w, v.len())).collect();
+
+            let active_voters = all_votes
+                .inner_join(all_items)
+                .filter(item_status.eq(ItemStatus::Open))
+                .select(vote_user_id)
+                .distinct()
+                .load::<i32>(c)
+                .context("Failed to read votes from db.")?
+                .len();
+
+            let items = all_items
+                .filter(item_status.ne_all([ItemStatus::Pending, ItemStatus::Rejected]))
+                .select((
+                    item_proposed_by,
+                    item_status,
+                    item_created_at,
+                    item_discussed_on,
+                ))
+                .load::<(Option<i32>, ItemStatus, NaiveDateTime, Option<NaiveDate>)>(c)
+                .context("Failed to read items from db.")?;
+            let mut proposed = HashMap::new();
+            let mut discussed = HashMap::new();
+            let mut by_user: HashMap<i32, (usize, usize)> = HashMap::new();
+            let mut days = Vec::new();
+            for (proposer, status, created, date) in items {
+                let created = item::known_creation(created);
+                if let Some(created) = created {
+                    *proposed.entry(month_of(created.date())).or_insert(0) += 1;
+                }
+                let entry = proposer.map(|uid| by_user.entry(uid).or_default());
+                let was_discussed = matches!(status, ItemStatus::Discussed | ItemStatus::FollowUp);
+                if let Some((p, d)) = entry {
+                    *p += 1;
+                    *d += was_discussed as usize;
+                }
+                if let Some(date) = date.filter(|d| was_discussed && *d <= today) {
+                    *discussed.entry(month_of(date)).or_insert(0) += 1;
+                    // items added after their meeting, e.g. to record old meetings, are skipped
+                    if let Some(created) = created.filter(|c| c.date() <= date) {
+                        days.push((date - created.date()).num_days() as f64);
+                    }
+                }
+            }
+            let avg_days_to_discussion =
+                (!days.is_empty()).then(|| days.iter().sum::<f64>() / days.len() as f64);
+
+            let names: HashMap<i32, String> = all_users
+                .filter(user_id.eq_any(by_user.keys().copied().collect::<Vec<_>>()))
+                .select((user_id, user_username))
+                .load::<(i32, String)>(c)
+                .context("Failed to read users from db.")?
+                .into_iter()
+                .collect();
+            let mut top_proposers: Vec<Proposer> = by_user
+                .into_iter()
+                .filter_map(|(uid, (p, d))| {
+                    Some(Proposer {
+                        username: names.get(&uid)?.clone(),
+                        proposed: p,
+                        discussed: d,
+                    })
+                })
+                .collect();
+            top_proposers.sort_by(|a, b| {
+                (b.proposed, b.discussed, &a.username).cmp(&(a.proposed, a.discussed, &b.username))
+            });
+            top_proposers.truncate(TOP_PROPOSERS);
+
+            Ok(ActivityStats {
+                ballots_per_week: periods(weeks.clone(), &ballot_counts),
+                voters_per_week: periods(weeks, &voter_counts),
+                active_voters,
+                proposed_per_month: periods(months.clone(), &proposed),
+                discussed_per_month: periods(months, &discussed),
+                avg_days_to_discussion,
+                top_proposers,
+            })
+        })
+        .await
+    }
+}
@@ -0,0 +1,291 @@
+use super::*;
+
+use anyhow::Error;
+use chrono::{NaiveDateTime, Utc};
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::sqlite::{Sqlite, SqliteValue};
+use rocket::form::{self, FromFormField, ValueField};
+use std::fmt;
+use std::str::FromStr;
+
+/// Lifecycle state of an item.
+///
+/// Only `Open` items are part of the ballot and the election. Every change of the status is
+/// recorded in the `status_changes` table together with its timestamp. Stale open items become
+/// `Expired` (see `Item::expire_stale`) until they are revived. With moderation enabled, new items
+/// are `Pending` until an admin approves them.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Text)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ItemStatus {
+    Open,
+    Scheduled,
+    Discussed,
+    FollowUp,
+    Postponed,
+    Withdrawn,
+    Rejected,
+    Expired,
+    Pending,
+}
+
+#[derive(Serialize, Queryable, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct StatusChange {
+    pub id: i32,
+    pub item_id: i32,
+    pub old_status: ItemStatus,
+    pub new_status: ItemStatus,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = self::schema::status_changes)]
+struct NewStatusChange {
+    item_id: i32,
+    old_status: ItemStatus,
+    new_status: ItemStatus,
+    changed_at: NaiveDateTime,
+}
+
+impl ItemStatus {
+    pub const ALL: [ItemStatus; 9] = [
+        ItemStatus::Open,
+        ItemStatus::Scheduled,
+        ItemStatus::Discussed,
+        ItemStatus::FollowUp,
+        ItemStatus::Postponed,
+        ItemStatus::Withdrawn,
+        ItemStatus::Rejected,
+        ItemStatus::Expired,
+        ItemStatus::Pending,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemStatus::Open => "open",
+            ItemStatus::Scheduled => "scheduled",
+            ItemStatus::Discussed => "discussed",
+            ItemStatus::FollowUp => "follow_up",
+            ItemStatus::Postponed => "postponed",
+            ItemStatus::Withdrawn => "withdrawn",
+            ItemStatus::Rejected => "rejected",
+            ItemStatus::Expired => "expired",
+            ItemStatus::Pending => "pending",
+        }
+    }
+
+    /// States which can be reached from this state.
+    pub fn transitions(&self) -> &'static [ItemStatus] {
+        use ItemStatus::*;
+        match self {
+            Open => &[Scheduled, Postponed, Withdrawn, Rejected, Expired],
+            Scheduled => &[Open, Discussed, FollowUp, Postponed],
+            Discussed => &[FollowUp],
+            FollowUp => &[Scheduled, Discussed],
+            Postponed => &[Open, Withdrawn, Rejected],
+            Withdrawn => &[Open],
+            Rejected => &[Open],
+            Expired => &[Open, Withdrawn],
+            Pending => &[Open, Rejected],
+        }
+    }
+
+    pub fn can_transition_to(&self, to: ItemStatus) -> bool {
+        self.transitions().contains(&to)
+    }
+
+    /// Whether an item in this state carries a meaningful `discussed_on` date.
+    pub fn has_date(&self) -> bool {
+        matches!(
+            self,
+            ItemStatus::Scheduled | ItemStatus::Discussed | ItemStatus::FollowUp
+        )
+    }
+}
+
+impl fmt::Display for ItemStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ItemStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ItemStatus::ALL
+            .iter()
+            .find(|status| status.as_str() == s.replace('-', "_"))
+            .copied()
+            .ok_or_else(|| Error::msg(format!("Unknown item status: {}", s)))
+    }
+}
+
+impl ToSql<Text, Sqlite> for ItemStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Sqlite> for ItemStatus {
+    fn from_sql(value: SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+        let s = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        Ok(s.parse()?)
+    }
+}
+
+#[rocket::async_trait]
+impl<'v> FromFormField<'v> for ItemStatus {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        field
+            .value
+            .parse()
+            .map_err(|e: Error| form::Error::validation(e.to_string()).into())
+    }
+}
+
+impl StatusChange {
+    pub async fn for_item(id: i32, conn: &DbConn) -> Vec<StatusChange> {
+        use self::schema::status_changes::dsl::{changed_at, item_id, status_changes};
+
+        conn.run(move |c| {
+            status_changes
+                .filter(item_id.eq(id))
+                .order(changed_at.asc())
+                .load::<StatusChange>(c)
+                .unwrap_or_default()
+        })
+        .await
+    }
+
+    /// Moves item `id` into `new_status` and records the transition.
+    ///
+    /// Fails if the transition is not allowed by the state machine. Must be called inside a
+    /// transaction together with any other change belonging to the transition.
+    pub(crate) fn apply(
+        id: i32,
+        new_status: ItemStatus,
+        c: &mut SqliteConnection,
+    ) -> Result<ItemStatus> {
+        use self::schema::items::dsl::status;
+
+        let old_status = all_items
+            .filter(item_id.eq(id))
+            .select(status)
+            .get_result::<ItemStatus>(c)
+            .context("Item not found in db.")?;
+
+        if old_status == new_status {
+            return Ok(old_status);
+        }
+        if !old_status.can_transition_to(new_status) {
+            return Err(Error::msg(format!(
+                "Item cannot change from {} to {}.",
+                old_status, new_status
+            )));
+        }
+
+        diesel::update(all_items.filter(item_id.eq(id)))
+            .set(status.eq(new_status))
+            .execute(c)
+            .context("Failed to update item status in db.")?;
+        diesel::insert_into(self::schema::status_changes::table)
+            .values(NewStatusChange {
+                item_id: id,
+                old_status,
+                new_status,
+                changed_at: Utc::now().naive_utc(),
+            })
+            .execute(c)
+            .context("Failed to record status change in db.")?;
+        Ok(old_status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_of(id: i32, c: &mut SqliteConnection) -> ItemStatus {
+        all_items
+            .filter(item_id.eq(id))
+            .select(item_status)
+            .get_result(c)
+            .unwrap()
+    }
+
+    fn changes(c: &mut SqliteConnection) -> Vec<(ItemStatus, ItemStatus)> {
+        use self::schema::status_changes::dsl::{id, new_status, old_status, status_changes};
+        status_changes
+            .order(id.asc())
+            .select((old_status, new_status))
+            .load(c)
+            .unwrap()
+    }
+
+    #[test]
+    fn transitions() {
+        use ItemStatus::*;
+        assert!(Open.can_transition_to(Scheduled));
+        assert!(Scheduled.can_transition_to(Open));
+        assert!(Expired.can_transition_to(Open));
+        assert!(Pending.can_transition_to(Rejected));
+        assert!(!Open.can_transition_to(Discussed));
+        assert!(!Discussed.can_transition_to(Open));
+        assert!(!Expired.can_transition_to(Scheduled));
+        for status in ItemStatus::ALL {
+            assert!(!status.can_transition_to(status), "{}", status);
+            assert!(!status.can_transition_to(Pending), "{}", status);
+            assert_eq!(status.as_str().parse::<ItemStatus>().unwrap(), status);
+        }
+        assert_eq!("follow-up".parse::<ItemStatus>().unwrap(), FollowUp);
+        assert!("closed".parse::<ItemStatus>().is_err());
+    }
+
+    #[test]
+    fn apply_records_allowed_changes() {
+        let c = &mut test_connection();
+        diesel::sql_query("INSERT INTO items (id, title, html, markdown) VALUES (1, 'a', '', '')")
+            .execute(c)
+            .unwrap();
+
+        assert_eq!(
+            StatusChange::apply(1, ItemStatus::Scheduled, c).unwrap(),
+            ItemStatus::Open
+        );
+        assert_eq!(status_of(1, c), ItemStatus::Scheduled);
+        // staying in the same state is not a change
+        StatusChange::apply(1, ItemStatus::Scheduled, c).unwrap();
+        StatusChange::apply(1, ItemStatus::Discussed, c).unwrap();
+        assert_eq!(
+            changes(c),
+            vec![
+                (ItemStatus::Open, ItemStatus::Scheduled),
+                (ItemStatus::Scheduled, ItemStatus::Discussed)
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_rejects_invalid_changes() {
+        let c = &mut test_connection();
+        diesel::sql_query(
+            "INSERT INTO items (id, title, html, markdown, status) VALUES (1, 'a', '', '', 'discussed')",
+        )
+        .execute(c)
+        .unwrap();
+
+        assert!(StatusChange::apply(1, ItemStatus::Open, c).is_err());
+        assert!(StatusChange::apply(1, ItemStatus::Expired, c).is_err());
+        assert!(StatusChange::apply(2, ItemStatus::Open, c).is_err());
+        assert_eq!(status_of(1, c), ItemStatus::Discussed);
+        assert!(changes(c).is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+use super::*;
+
+use self::schema::item_tags::dsl::{item_id as tag_item_id, item_tags, tag_id};
+use self::schema::tags::dsl::{id as tags_id, name, tags};
+use std::collections::HashMap;
+
+/// Names of tags as entered in the item form, e.g. "Computer Vision, nlp".
+///
+/// Tags are lowercase without surrounding whitespace and with inner whitespace replaced by
+/// dashes, so "Computer  Vision" and "computer-vision" are the same tag.
+pub fn parse(input: &str) -> Vec<String> {
+    let mut names: Vec<String> = input
+        .split(',')
+        .map(normalize)
+        .filter(|t| !t.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+pub fn normalize(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+pub struct Tag;
+
+impl Tag {
+    /// Names of all tags used by at least one item, sorted by name.
+    pub async fn all(conn: &DbConn) -> Vec<String> {
+        conn.run(move |c| {
+            tags.inner_join(item_tags)
+                .select(name)
+                .distinct()
+                .order(name.asc())
+                .load::<String>(c)
+                .unwrap_or_default()
+        })
+        .await
+    }
+
+    /// Tags of the given items by item id, every item has an entry even without tags.
+    pub async fn for_items(ids: Vec<i32>, conn: &DbConn) -> HashMap<i32, Vec<String>> {
+        conn.run(move |c| {
+            let mut map: HashMap<i32, Vec<String>> =
+                ids.iter().map(|id| (*id, Vec::new())).collect();
+            let rows = tags
+                .inner_join(item_tags)
+                .filter(tag_item_id.eq_any(ids))
+                .order(name.asc())
+                .select((tag_item_id, name))
+                .load::<(i32, String)>(c)
+                .unwrap_or_default();
+            for (id, tag) in rows {
+                map.entry(id).or_default().push(tag);
+            }
+            map
+        })
+        .await
+    }
+
+    pub async fn for_item(id: i32, conn: &DbConn) -> Vec<String> {
+        Tag::for_items(vec![id], conn)
+            .await
+            .remove(&id)
+            .unwrap_or_default()
+    }
+
+    /// Ids of the items tagged with `tag`.
+    pub(crate) fn items_with(tag: &str, c: &mut SqliteConnection) -> Result<Vec<i32>> {
+        item_tags
+            .inner_join(tags)
+            .filter(name.eq(normalize(tag)))
+            .select(tag_item_id)
+            .load::<i32>(c)
+            .context("Failed to read tags from db.")
+    }
+
+    /// Replaces the tags of item `id` and removes tags no item uses anymore.
+    pub(crate) fn set_for_item(id: i32, names: &[String], c: &mut SqliteConnection) -> Result<()> {
+        diesel::delete(item_tags.filter(tag_item_id.eq(id)))
+            .execute(c)
+            .context("Failed to update tags in db.")?;
+        for tag in names {
+            diesel::insert_or_ignore_into(tags)
+                .values(name.eq(tag))
+                .execute(c)
+                .context("Failed to insert tag into db.")?;
+            let tag = tags
+                .filter(name.eq(tag))
+                .select(tags_id)
+                .get_result::<i32>(c)
+                .context("Failed to insert tag into db.")?;
+            diesel::insert_or_ignore_into(item_tags)
+                .values((tag_item_id.eq(id), tag_id.eq(tag)))
+                .execute(c)
+                .context("Failed to update tags in db.")?;
+        }
+        Tag::remove_unused(c)
+    }
+
+    /// Copies the tags of item `from` to item `to`, used when merging items.
+    pub(crate) fn copy(from: i32, to: i32, c: &mut SqliteConnection) -> Result<()> {
+        let copied = item_tags
+            .filter(tag_item_id.eq(from))
+            .select(tag_id)
+            .load::<i32>(c)
+            .context("Failed to read tags from db.")?;
+        for tag in copied {
+            diesel::insert_or_ignore_into(item_tags)
+                .values((tag_item_id.eq(to), tag_id.eq(tag)))
+                .execute(c)
+                .context("Failed to move tags.")?;
+        }
+        Ok(())
+    }
+
+    /// Removes tags no item uses anymore.
+    pub(crate) fn remove_unused(c: &mut SqliteConnection) -> Result<()> {
+        let used = item_tags.select(tag_id).distinct();
+        diesel::delete(tags.filter(tags_id.ne_all(used)))
+            .execute(c)
+            .context("Failed to remove unused tags from db.")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use super::*;
+
+use endorsement::EndorsementConfig;
+use fairness::FairnessConfig;
+use item::Item;
+use vote::Vote;
+
+/// Tracks which get their own election, configured in `Rocket.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde", default)]
+pub struct TrackConfig {
+    /// tags of the tracks, e.g. ["vision", "nlp"]
+    pub tracks: Vec<String>,
+}
+
+/// Preliminary winner of the election of a single track.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct TrackWinner {
+    pub track: String,
+    /// `None` if nobody ranked an open item of the track
+    pub winner: Option<Item>,
+    /// see `Election::transcript`
+    pub transcript: Vec<String>,
+}
+
+impl Vote {
+    /// Runs one election per configured track on the same ballots, in the configured order.
+    pub async fn run_track_elections(
+        config: &TrackConfig,
+        fairness: &FairnessConfig,
+        endorsement: &EndorsementConfig,
+        conn: &DbConn,
+    ) -> Vec<TrackWinner> {
+        let mut winners = Vec::new();
+        for track in config.tracks.iter().map(|t| tag::normalize(t)) {
+            let election =
+                Vote::run_election(Some(track.clone()), fairness, endorsement, conn).await;
+            winners.push(TrackWinner {
+                winner: election.winner,
+                transcript: election.transcript,
+                track,
+            });
+        }
+        winners
+    }
+}
@@ -52,7 +52,7 @@ pub struct NewPassword {
 mod password {
     use super::*;
 
-    pub fn verify(password: &String, hash: &String) -> Result<()> {
+    pub fn verify(password: &str, hash: &str) -> Result<()> {
         // Verify password against PHC string
         let parsed_hash =
             PasswordHash::new(hash).map_err(|_| Error::msg("Failed reading password."))?;
@@ -62,7 +62,7 @@ mod password {
         Ok(())
     }
 
-    pub fn generate_new_hash(password: &String) -> Result<String> {
+    pub fn generate_new_hash(password: &str) -> Result<String> {
         let salt = SaltString::generate(&mut OsRng);
         // Hash password to PHC string ($pbkdf2-sha256$...)
         Ok(Pbkdf2
@@ -101,7 +101,7 @@ impl NewUser {
                 .filter(user_username.eq(&self.username))
                 .get_result::<UserDB>(c);
 
-            if let Ok(_) = user {
+            if user.is_ok() {
                 return Err(Error::msg("User already exists."));
             }
 
@@ -148,19 +148,18 @@ impl User {
 
     pub async fn from_ids(ids: Vec<i32>, conn: &DbConn) -> Result<Vec<User>> {
         conn.run(move |c| {
-            let users: QueryResult<Vec<UserDB>>;
-            if ids.len() > 0 {
-                users = all_users
+            let users = if !ids.is_empty() {
+                all_users
                     .filter(user_id.eq_any(ids))
-                    .get_results::<UserDB>(c);
+                    .get_results::<UserDB>(c)
             } else {
-                users = all_users.get_results::<UserDB>(c);
-            }
+                all_users.get_results::<UserDB>(c)
+            };
 
             let users = users
                 .context("Failed to retrieve users form db.")?
                 .into_iter()
-                .map(|u| User::from(u))
+                .map(User::from)
                 .collect();
             Ok(users)
         })
@@ -211,18 +210,15 @@ impl User {
 
     pub async fn set_admin(ids: Vec<i32>, value: bool, conn: &DbConn) -> Result<usize> {
         conn.run(move |c| {
-            let rows: QueryResult<usize>;
-            if ids.len() > 0 {
-                rows = diesel::update(
-                    all_users.filter(user_id.eq_any(ids).and(user_admin.eq(!value))),
-                )
-                .set(user_admin.eq(value))
-                .execute(c);
+            let rows = if !ids.is_empty() {
+                diesel::update(all_users.filter(user_id.eq_any(ids).and(user_admin.eq(!value))))
+                    .set(user_admin.eq(value))
+                    .execute(c)
             } else {
-                rows = diesel::update(all_users.filter(user_admin.eq(!value)))
+                diesel::update(all_users.filter(user_admin.eq(!value)))
                     .set(user_admin.eq(value))
-                    .execute(c);
-            }
+                    .execute(c)
+            };
             let rows = rows.context("Failed to set admin users in db.")?;
             Ok(rows)
         })
@@ -231,18 +227,15 @@ impl User {
 
     pub async fn set_approve(ids: Vec<i32>, value: bool, conn: &DbConn) -> Result<usize> {
         conn.run(move |c| {
-            let rows: QueryResult<usize>;
-            if ids.len() > 0 {
-                rows = diesel::update(
-                    all_users.filter(user_id.eq_any(ids).and(user_approved.eq(!value))),
-                )
-                .set(user_approved.eq(value))
-                .execute(c);
+            let rows = if !ids.is_empty() {
+                diesel::update(all_users.filter(user_id.eq_any(ids).and(user_approved.eq(!value))))
+                    .set(user_approved.eq(value))
+                    .execute(c)
             } else {
-                rows = diesel::update(all_users.filter(user_approved.eq(!value)))
+                diesel::update(all_users.filter(user_approved.eq(!value)))
                     .set(user_approved.eq(value))
-                    .execute(c);
-            }
+                    .execute(c)
+            };
             let rows = rows.context("Failed to approve users in db.")?;
             Ok(rows)
         })
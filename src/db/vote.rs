@@ -1,8 +1,14 @@
 use super::*;
 
+use self::schema::votes::dsl::voted_at;
+use chrono::Utc;
+use endorsement::{Endorsement, EndorsementConfig};
+use fairness::FairnessConfig;
 use item::Item;
 use itertools::Itertools;
 use rcir;
+use status::ItemStatus;
+use tag::Tag;
 
 #[derive(Queryable, Insertable, Debug, Clone)]
 #[diesel(table_name = self::schema::votes)]
@@ -17,75 +23,334 @@ pub struct Ballot {
     pub votes: Vec<i32>,
 }
 
+/// Outcome of an election, see `Vote::run_election`.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct Election {
+    pub winner: Option<Item>,
+    /// how the winner was found, including every winner skipped by the fairness rules
+    pub transcript: Vec<String>,
+}
+
 impl Vote {
-    pub async fn run_election(conn: &DbConn) -> Option<Item> {
-        conn.run(move |mut c| {
-            let votes = all_votes
+    /// Winner among the open items, only counting items tagged `track` if one is given.
+    ///
+    /// Every ballot is restricted to the items of the track, keeping their order.
+    pub async fn run_election(
+        track: Option<String>,
+        fairness: &FairnessConfig,
+        endorsement: &EndorsementConfig,
+        conn: &DbConn,
+    ) -> Election {
+        let fairness = fairness.clone();
+        let endorsement = endorsement.clone();
+        conn.run(move |c| {
+            let mut query = all_votes
                 .inner_join(all_items)
-                .filter(item_discussed_on.is_null())
+                .filter(item_status.eq(ItemStatus::Open))
                 .order((vote_user_id.asc(), ordinal.asc()))
                 .select((vote_user_id, vote_item_id, ordinal))
-                .get_results::<Vote>(c)
-                .ok()?;
+                .into_boxed();
+            if let Some(track) = track {
+                let Ok(ids) = Tag::items_with(&track, c) else {
+                    return Election::default();
+                };
+                query = query.filter(vote_item_id.eq_any(ids));
+            }
+            let votes = query.get_results::<Vote>(c).unwrap_or_default();
 
-            Vote::election_driver(&votes, &mut c)
+            Vote::fair_election(votes, &fairness, &endorsement, c)
         })
         .await
     }
 
-    pub async fn run_second_election(conn: &DbConn, winner: Option<Item>) -> Option<Item> {
-        conn.run(move |mut c| {
+    pub async fn run_second_election(
+        conn: &DbConn,
+        fairness: &FairnessConfig,
+        endorsement: &EndorsementConfig,
+        winner: Option<Item>,
+    ) -> Option<Item> {
+        let fairness = fairness.clone();
+        let endorsement = endorsement.clone();
+        conn.run(move |c| {
             let winner = winner.as_ref()?;
             let votes = all_votes
                 .inner_join(all_items)
-                .filter(item_discussed_on.is_null())
+                .filter(item_status.eq(ItemStatus::Open))
                 .filter(vote_item_id.ne(winner.id))
                 .order((vote_user_id.asc(), ordinal.asc()))
                 .select((vote_user_id, vote_item_id, ordinal))
                 .get_results::<Vote>(c)
                 .ok()?;
 
-            Vote::election_driver(&votes, &mut c)
+            Vote::fair_election(votes, &fairness, &endorsement, c).winner
         })
         .await
     }
 
-    fn election_driver(votes: &Vec<Vote>, c: &mut SqliteConnection) -> Option<Item> {
+    /// Runs the election until the winner is eligible under the fairness rules.
+    ///
+    /// Items without enough endorsements are not on the ranked ballot and cannot win. An
+    /// ineligible winner is removed from all ballots and the election is repeated, the transcript
+    /// explains every skipped winner.
+    fn fair_election(
+        mut votes: Vec<Vote>,
+        fairness: &FairnessConfig,
+        endorsement: &EndorsementConfig,
+        c: &mut SqliteConnection,
+    ) -> Election {
+        if endorsement.endorsements_to_rank > 0 {
+            let counts = Endorsement::counts_in(c);
+            votes.retain(|v| endorsement.is_ranked(counts.get(&v.item_id).copied().unwrap_or(0)));
+        }
+        let mut transcript = Vec::new();
+        loop {
+            let Some(winner) = Vote::election_driver(&votes, c) else {
+                if !transcript.is_empty() {
+                    transcript.push("No eligible item is left.".to_string());
+                }
+                return Election {
+                    winner: None,
+                    transcript,
+                };
+            };
+            match fairness.check(&winner, c) {
+                Ok(Some(reason)) => {
+                    transcript.push(format!(
+                        "\"{}\" won the count but is skipped: {}",
+                        winner.title, reason
+                    ));
+                    votes.retain(|v| v.item_id != winner.id);
+                }
+                Ok(None) => {
+                    transcript.push(format!("\"{}\" wins.", winner.title));
+                    return Election {
+                        winner: Some(winner),
+                        transcript,
+                    };
+                }
+                Err(e) => {
+                    transcript.push(format!("The fairness rules could not be checked: {}", e));
+                    return Election {
+                        winner: Some(winner),
+                        transcript,
+                    };
+                }
+            }
+        }
+    }
+
+    fn election_driver(votes: &[Vote], c: &mut SqliteConnection) -> Option<Item> {
         // the extra collections here are sad.
         let votes: Vec<Vec<_>> = votes
-            .into_iter()
+            .iter()
             .group_by(|v| v.user_id)
             .into_iter()
             .map(|(_, ballot)| ballot.into_iter().map(|v| v.item_id).collect())
             .collect();
 
         match rcir::run_election(&votes, rcir::MajorityMode::RemainingMajority).ok()? {
-            rcir::ElectionResult::Winner(&iid) => all_items.find(iid).get_result::<Item>(c).ok(),
+            rcir::ElectionResult::Winner(&iid) => Item::query()
+                .filter(item_id.eq(iid))
+                .get_result::<Item>(c)
+                .ok(),
             rcir::ElectionResult::Tie(iids) => {
                 // TODO: maybe pick the oldest one?
-                all_items.find(*iids[0]).get_result::<Item>(c).ok()
+                Item::query()
+                    .filter(item_id.eq(*iids[0]))
+                    .get_result::<Item>(c)
+                    .ok()
             }
         }
     }
 
+    /// Replaces the ballot of `uid` and logs the submission for the statistics.
     pub async fn save_ballot(uid: i32, ballot: Ballot, conn: &DbConn) -> Result<()> {
+        use self::schema::ballots::dsl::{ballots, submitted_at, user_id as ballot_user_id};
+
         conn.run(move |c| {
-            diesel::delete(all_votes.filter(vote_user_id.eq(&uid)))
-                .execute(c)
-                .context("Faile to write save ballow.")?;
+            c.transaction(|c| {
+                Vote::write_ballot(uid, &ballot.votes, c)?;
+                diesel::insert_into(ballots)
+                    .values((
+                        ballot_user_id.eq(uid),
+                        submitted_at.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(c)
+                    .context("Failed to log ballot in db.")?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Replaces the ballot of `uid` with `votes`, numbering the ordinals from 0.
+    ///
+    /// Every vote is stamped with the time of the ballot, see `Item::expire_stale`. Only open
+    /// items are on the ballot, the ranks of other items (e.g. expired ones which may be revived)
+    /// are kept after the new ones.
+    pub(crate) fn write_ballot(uid: i32, votes: &[i32], c: &mut SqliteConnection) -> Result<()> {
+        let kept = all_votes
+            .inner_join(all_items)
+            .filter(vote_user_id.eq(uid))
+            .filter(item_status.ne(ItemStatus::Open))
+            .filter(vote_item_id.ne_all(votes))
+            .order(ordinal.asc())
+            .select(vote_item_id)
+            .load::<i32>(c)
+            .context("Faile to write save ballow.")?;
+        diesel::delete(
+            all_votes
+                .filter(vote_user_id.eq(&uid))
+                .filter(vote_item_id.ne_all(&kept)),
+        )
+        .execute(c)
+        .context("Faile to write save ballow.")?;
+        for (i, iid) in kept.iter().enumerate() {
+            diesel::update(
+                all_votes
+                    .filter(vote_user_id.eq(uid))
+                    .filter(vote_item_id.eq(iid)),
+            )
+            .set(ordinal.eq((votes.len() + i) as i32))
+            .execute(c)
+            .context("Faile to write save ballow.")?;
+        }
 
-            for (i, iid) in ballot.votes.into_iter().enumerate() {
-                diesel::insert_into(all_votes)
-                    .values(Vote {
+        let now = Utc::now().naive_utc();
+        for (i, iid) in votes.iter().enumerate() {
+            diesel::insert_into(all_votes)
+                .values((
+                    Vote {
                         user_id: uid,
-                        item_id: iid,
+                        item_id: *iid,
                         ordinal: i as i32,
-                    })
+                    },
+                    voted_at.eq(now),
+                ))
+                .execute(c)
+                .context("Faile to write save ballow.")?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites all ballots ranking `drop` so they rank `keep` instead.
+    ///
+    /// Users who ranked both items keep the better of the two ranks. The rows are updated in
+    /// place, so the time of the vote is kept.
+    pub(crate) fn merge_ballots(keep: i32, drop: i32, c: &mut SqliteConnection) -> Result<()> {
+        let voters = all_votes
+            .filter(vote_item_id.eq(drop))
+            .select((vote_user_id, ordinal))
+            .load::<(i32, i32)>(c)
+            .context("Failed to read ballots from db.")?;
+
+        for (uid, drop_rank) in voters {
+            let keep_rank = all_votes
+                .filter(vote_user_id.eq(uid))
+                .filter(vote_item_id.eq(keep))
+                .select(ordinal)
+                .first::<i32>(c)
+                .optional()
+                .context("Failed to read ballots from db.")?;
+
+            let drop_vote = all_votes
+                .filter(vote_user_id.eq(uid))
+                .filter(vote_item_id.eq(drop));
+            match keep_rank {
+                Some(keep_rank) => {
+                    if drop_rank < keep_rank {
+                        diesel::update(
+                            all_votes
+                                .filter(vote_user_id.eq(uid))
+                                .filter(vote_item_id.eq(keep)),
+                        )
+                        .set(ordinal.eq(drop_rank))
+                        .execute(c)
+                        .context("Failed to merge ballots.")?;
+                    }
+                    diesel::delete(drop_vote)
+                        .execute(c)
+                        .context("Failed to merge ballots.")?;
+                }
+                None => {
+                    diesel::update(drop_vote)
+                        .set(vote_item_id.eq(keep))
+                        .execute(c)
+                        .context("Failed to merge ballots.")?;
+                }
+            }
+
+            // close the gap left by the removed rank
+            let ballot = all_votes
+                .filter(vote_user_id.eq(uid))
+                .order(ordinal.asc())
+                .select((vote_item_id, ordinal))
+                .load::<(i32, i32)>(c)
+                .context("Failed to read ballots from db.")?;
+            for (i, (iid, rank)) in ballot.into_iter().enumerate() {
+                if rank != i as i32 {
+                    diesel::update(
+                        all_votes
+                            .filter(vote_user_id.eq(uid))
+                            .filter(vote_item_id.eq(iid)),
+                    )
+                    .set(ordinal.eq(i as i32))
                     .execute(c)
-                    .context("Faile to write save ballow.")?;
+                    .context("Failed to merge ballots.")?;
+                }
             }
-            Ok(())
-        })
-        .await
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cast(uid: i32, items: &[i32], c: &mut SqliteConnection) {
+        for (rank, id) in items.iter().enumerate() {
+            diesel::insert_into(all_votes)
+                .values((
+                    vote_user_id.eq(uid),
+                    vote_item_id.eq(id),
+                    ordinal.eq(rank as i32),
+                ))
+                .execute(c)
+                .unwrap();
+        }
+    }
+
+    fn ballot(uid: i32, c: &mut SqliteConnection) -> Vec<(i32, i32)> {
+        all_votes
+            .filter(vote_user_id.eq(uid))
+            .order(ordinal.asc())
+            .select((vote_item_id, ordinal))
+            .load(c)
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_ballots_keeps_better_rank() {
+        let c = &mut test_connection();
+        // item 1 is merged into item 3
+        cast(1, &[1, 2, 3, 4], c);
+        cast(2, &[3, 1, 4], c);
+        Vote::merge_ballots(3, 1, c).unwrap();
+
+        assert_eq!(ballot(1, c), vec![(3, 0), (2, 1), (4, 2)]);
+        assert_eq!(ballot(2, c), vec![(3, 0), (4, 1)]);
+    }
+
+    #[test]
+    fn merge_ballots_moves_votes() {
+        let c = &mut test_connection();
+        cast(1, &[2, 1, 4], c);
+        cast(2, &[4, 2], c);
+        Vote::merge_ballots(3, 1, c).unwrap();
+
+        assert_eq!(ballot(1, c), vec![(2, 0), (3, 1), (4, 2)]);
+        assert_eq!(ballot(2, c), vec![(4, 0), (2, 1)]);
     }
 }
@@ -0,0 +1,251 @@
+use comrak::nodes::{AstNode, NodeValue};
+use comrak::{parse_document, Arena, ComrakOptions};
+use rocket::serde::Serialize;
+use std::collections::HashSet;
+
+use crate::item::Item;
+use crate::status::ItemStatus;
+use crate::DbConn;
+use chrono::NaiveDate;
+
+/// Titles at least this similar are reported as likely duplicates.
+const TITLE_SIMILARITY: f64 = 0.85;
+
+/// Placeholder links of the new item template which are not specific to any paper.
+const TEMPLATE_LINKS: [&str; 3] = ["link1.com", "link2.com", "link3.com"];
+
+/// An existing item which likely describes the same paper as a new submission.
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Duplicate {
+    pub id: i32,
+    pub title: String,
+    pub status: ItemStatus,
+    pub discussed_on: Option<NaiveDate>,
+    pub reasons: Vec<String>,
+}
+
+/// Identifier of a paper found in a link.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Identifier {
+    Arxiv(String),
+    Doi(String),
+    Url(String),
+}
+
+impl std::fmt::Display for Identifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Identifier::Arxiv(id) => write!(f, "arXiv:{}", id),
+            Identifier::Doi(doi) => write!(f, "DOI {}", doi),
+            Identifier::Url(url) => write!(f, "link {}", url),
+        }
+    }
+}
+
+/// Lower case alphanumeric words of a title.
+pub fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Similarity of two titles between 0 and 1.
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_title(a), normalize_title(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let words_a: HashSet<_> = a.split(' ').collect();
+    let words_b: HashSet<_> = b.split(' ').collect();
+    let jaccard =
+        words_a.intersection(&words_b).count() as f64 / words_a.union(&words_b).count() as f64;
+    jaccard.max(strsim::normalized_levenshtein(&a, &b))
+}
+
+fn strip_version(arxiv_id: &str) -> &str {
+    match arxiv_id.rsplit_once('v') {
+        Some((id, version))
+            if !id.is_empty()
+                && !version.is_empty()
+                && version.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            id
+        }
+        _ => arxiv_id,
+    }
+}
+
+/// Whether `id` is an arXiv ID, either new style like `2101.12345v2` or old style like
+/// `hep-th/9901001` or `math.GT/0309136`.
+pub fn is_arxiv_id(id: &str) -> bool {
+    let digits = |s: &str, len: std::ops::RangeInclusive<usize>| {
+        len.contains(&s.len()) && s.chars().all(|c| c.is_ascii_digit())
+    };
+    let id = strip_version(id);
+    match id.split_once('/') {
+        Some((archive, number)) => {
+            let (archive, class) = archive.split_once('.').unwrap_or((archive, "AA"));
+            !archive.is_empty()
+                && archive.chars().all(|c| c.is_ascii_lowercase() || c == '-')
+                && class.len() == 2
+                && class.chars().all(|c| c.is_ascii_uppercase())
+                && digits(number, 7..=7)
+        }
+        None => match id.split_once('.') {
+            Some((month, number)) => digits(month, 4..=4) && digits(number, 4..=5),
+            None => false,
+        },
+    }
+}
+
+fn is_doi_prefix(segment: &str) -> bool {
+    segment.strip_prefix("10.").is_some_and(|registrant| {
+        !registrant.is_empty() && registrant.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+// lower case host without `www.` and the path of a http(s) link, without query and fragment
+fn split_link(url: &str) -> Option<(String, &str)> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let rest = rest.split(['?', '#']).next().unwrap_or(rest);
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let host = host.to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
+    Some((host, path.trim_end_matches('/')))
+}
+
+// arXiv abstract pages and PDFs
+fn arxiv_in<'a>(host: &str, path: &'a str) -> Option<&'a str> {
+    if host != "arxiv.org" && !host.ends_with(".arxiv.org") {
+        return None;
+    }
+    path.strip_prefix("abs/")
+        .or_else(|| path.strip_prefix("pdf/"))
+        .map(|id| id.trim_end_matches(".pdf"))
+}
+
+// doi.org links as well as publishers like ACM or Wiley have the DOI in the path
+fn doi_in(path: &str) -> Option<&str> {
+    let mut rest = path;
+    loop {
+        let (segment, tail) = rest.split_once('/')?;
+        if is_doi_prefix(segment) && !tail.is_empty() {
+            return Some(rest);
+        }
+        rest = tail;
+    }
+}
+
+/// DOI given as is, as `doi:10.1000/xyz` or as link like `https://doi.org/10.1000/xyz`, `None`
+/// if empty.
+///
+/// Links without a DOI are returned unchanged, so they fail validation.
+pub fn clean_doi(doi: &str) -> Option<String> {
+    let doi = doi.trim();
+    let doi = match split_link(doi) {
+        Some((_, path)) => doi_in(path).unwrap_or(doi),
+        None => doi.strip_prefix("doi:").unwrap_or(doi),
+    };
+    (!doi.is_empty()).then(|| doi.to_string())
+}
+
+/// arXiv ID given as is, as `arXiv:2101.12345` or as link to the abstract page or PDF, `None` if
+/// empty.
+///
+/// Links without an arXiv ID are returned unchanged, so they fail validation.
+pub fn clean_arxiv(id: &str) -> Option<String> {
+    let id = id.trim();
+    let id = match split_link(id) {
+        Some((host, path)) => arxiv_in(&host, path).unwrap_or(id),
+        None => id
+            .strip_prefix("arXiv:")
+            .or_else(|| id.strip_prefix("arxiv:"))
+            .unwrap_or(id),
+    };
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// Classifies a link target as arXiv ID, DOI or plain URL.
+pub fn identify(url: &str) -> Option<Identifier> {
+    let (host, path) = split_link(url.trim())?;
+
+    if TEMPLATE_LINKS.contains(&host.as_str()) {
+        return None;
+    }
+    if let Some(id) = arxiv_in(&host, path) {
+        return Some(Identifier::Arxiv(strip_version(id).to_string()));
+    }
+    if let Some(doi) = doi_in(path) {
+        return Some(Identifier::Doi(doi.to_lowercase()));
+    }
+    Some(Identifier::Url(
+        format!("{}/{}", host, path)
+            .trim_end_matches('/')
+            .to_string(),
+    ))
+}
+
+/// Identifiers of all links in a markdown document.
+pub fn identifiers(markdown: &str) -> HashSet<Identifier> {
+    let arena = Arena::new();
+    let root = parse_document(&arena, markdown, &ComrakOptions::default());
+
+    fn collect<'a>(node: &'a AstNode<'a>, ids: &mut HashSet<Identifier>) {
+        if let NodeValue::Link(ref link) = node.data.borrow().value {
+            if let Some(id) = identify(&String::from_utf8_lossy(&link.url)) {
+                ids.insert(id);
+            }
+        }
+        for c in node.children() {
+            collect(c, ids);
+        }
+    }
+
+    let mut ids = HashSet::new();
+    collect(root, &mut ids);
+    ids
+}
+
+/// Compares a submission against the given items.
+///
+/// `markdown` is the markdown of the whole card, see `Bibliography::compose`.
+pub fn find_in(title: &str, markdown: &str, items: &[Item]) -> Vec<Duplicate> {
+    let ids = identifiers(markdown);
+    items
+        .iter()
+        .filter_map(|item| {
+            let mut reasons = Vec::new();
+            let similarity = title_similarity(title, &item.title);
+            if similarity >= TITLE_SIMILARITY {
+                reasons.push(format!("similar title ({:.0}%)", similarity * 100.0));
+            }
+            let mut shared: Vec<_> = identifiers(&item.card_markdown())
+                .intersection(&ids)
+                .map(|id| format!("same {}", id))
+                .collect();
+            shared.sort();
+            reasons.extend(shared);
+
+            (!reasons.is_empty()).then(|| Duplicate {
+                id: item.id,
+                title: item.title.clone(),
+                status: item.status,
+                discussed_on: item.discussed_on,
+                reasons,
+            })
+        })
+        .collect()
+}
+
+/// Existing items which likely describe the same paper as a new submission.
+pub async fn find(title: &str, markdown: &str, conn: &DbConn) -> Vec<Duplicate> {
+    let items = Item::get_all(conn).await;
+    find_in(title, markdown, &items)
+}
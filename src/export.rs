@@ -0,0 +1,219 @@
+use anyhow::{Context, Error, Result};
+use rocket::http::ContentType;
+use rocket::serde::json;
+use std::str::FromStr;
+
+use crate::duplicate::{self, Identifier};
+use crate::item::{self, Item};
+
+/// Formats item lists can be exported to.
+#[derive(FromFormField, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Bibtex,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format> {
+        match s.to_lowercase().as_str() {
+            "bib" | "bibtex" => Ok(Format::Bibtex),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(Error::msg(format!("Unknown format: {}", s))),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Bibtex => "bib",
+            Format::Csv => "csv",
+            Format::Json => "json",
+        }
+    }
+
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            Format::Bibtex => ContentType::new("application", "x-bibtex"),
+            Format::Csv => ContentType::CSV,
+            Format::Json => ContentType::JSON,
+        }
+    }
+}
+
+pub fn export(items: &[Item], format: Format) -> Result<String> {
+    match format {
+        Format::Bibtex => Ok(to_bibtex(items)),
+        Format::Csv => Ok(to_csv(items)),
+        Format::Json => json::to_pretty_string(&items).context("Failed to serialize items."),
+    }
+}
+
+// cells starting like a formula are prefixed with a quote, so spreadsheets show them as text
+pub(crate) fn csv_escape(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+pub fn to_csv(items: &[Item]) -> String {
+    let mut csv = String::from(
+        "id,title,status,proposer,created_at,discussed_on,authors,year,venue,doi,arxiv_id,url,links\n",
+    );
+    for item in items {
+        let mut links: Vec<_> = duplicate::identifiers(&item.card_markdown())
+            .iter()
+            .map(link)
+            .collect();
+        links.sort();
+        let bib = &item.bib;
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            item.id,
+            csv_escape(&item.title),
+            item.status,
+            csv_escape(item.proposer.as_deref().unwrap_or("")),
+            item::known_creation(item.created_at)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default(),
+            item.discussed_on.map(|d| d.to_string()).unwrap_or_default(),
+            csv_escape(bib.authors.as_deref().unwrap_or("")),
+            bib.year.map(|y| y.to_string()).unwrap_or_default(),
+            csv_escape(bib.venue.as_deref().unwrap_or("")),
+            csv_escape(bib.doi.as_deref().unwrap_or("")),
+            csv_escape(bib.arxiv_id.as_deref().unwrap_or("")),
+            csv_escape(bib.url.as_deref().unwrap_or("")),
+            csv_escape(&links.join(" "))
+        ));
+    }
+    csv
+}
+
+fn link(id: &Identifier) -> String {
+    match id {
+        Identifier::Arxiv(id) => format!("https://arxiv.org/abs/{}", id),
+        Identifier::Doi(doi) => format!("https://doi.org/{}", doi),
+        Identifier::Url(url) => format!("https://{}", url),
+    }
+}
+
+fn bibtex_escape(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            c if "&%$#_{}".contains(c) => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+// identifiers and links are kept as they are, only characters breaking the entry are encoded
+fn bibtex_link(link: &str) -> String {
+    link.replace('\\', "%5C")
+        .replace('{', "%7B")
+        .replace('}', "%7D")
+}
+
+// items without bibliographic fields start with "Author et al., Year"
+fn authors_and_year(markdown: &str) -> (Option<String>, Option<String>) {
+    let line = markdown.lines().map(str::trim).find(|l| !l.is_empty());
+    let Some(line) = line else {
+        return (None, None);
+    };
+    let (authors, year) = match line.rsplit_once(',') {
+        Some((authors, year))
+            if year.trim().len() == 4 && year.trim().chars().all(|c| c.is_ascii_digit()) =>
+        {
+            (authors.trim(), Some(year.trim().to_string()))
+        }
+        _ => return (None, None),
+    };
+    let authors = match authors.strip_suffix("et al.") {
+        Some(first) => format!("{} and others", first.trim()),
+        None => authors.to_string(),
+    };
+    (Some(authors), year)
+}
+
+fn bibtex_fields(item: &Item) -> Vec<(&'static str, String)> {
+    let bib = &item.bib;
+    let mut fields = vec![("title", format!("{{{}}}", bibtex_escape(&item.title)))];
+    let (authors, year) = match (bib.authors.is_some(), bib.year) {
+        (false, None) => authors_and_year(&item.markdown),
+        _ => (
+            Some(bib.author_list().join(" and ")).filter(|a| !a.is_empty()),
+            bib.year.map(|y| y.to_string()),
+        ),
+    };
+    if let Some(authors) = authors {
+        fields.push(("author", bibtex_escape(&authors)));
+    }
+    if let Some(year) = year {
+        fields.push(("year", year));
+    }
+    if let Some(venue) = &bib.venue {
+        fields.push(("howpublished", bibtex_escape(venue)));
+    }
+
+    // links in the notes of older items
+    let mut ids: Vec<_> = duplicate::identifiers(&item.markdown).into_iter().collect();
+    ids.sort_by_key(|id| id.to_string());
+    let doi = bib.doi.clone().or_else(|| {
+        ids.iter().find_map(|id| match id {
+            Identifier::Doi(doi) => Some(doi.clone()),
+            _ => None,
+        })
+    });
+    let arxiv = bib.arxiv_id.clone().or_else(|| {
+        ids.iter().find_map(|id| match id {
+            Identifier::Arxiv(id) => Some(id.clone()),
+            _ => None,
+        })
+    });
+    let url = bib.url.clone().or_else(|| {
+        ids.iter()
+            .find(|id| matches!(id, Identifier::Url(_)))
+            .map(link)
+    });
+    if let Some(doi) = doi {
+        fields.push(("doi", bibtex_link(&doi)));
+    }
+    if let Some(id) = arxiv {
+        fields.push(("eprint", bibtex_link(&id)));
+        fields.push(("archiveprefix", "arXiv".to_string()));
+    }
+    if let Some(url) = url {
+        fields.push(("url", bibtex_link(&url)));
+    }
+    if let Some(date) = item.discussed_on {
+        fields.push(("note", format!("Discussed on {}", date)));
+    }
+    fields
+}
+
+pub fn to_bibtex(items: &[Item]) -> String {
+    let mut bibtex = String::new();
+    for item in items {
+        bibtex.push_str(&format!("@misc{{prank{},\n", item.id));
+        for (name, value) in bibtex_fields(item) {
+            bibtex.push_str(&format!("  {} = {{{}}},\n", name, value));
+        }
+        bibtex.push_str("}\n\n");
+    }
+    bibtex
+}
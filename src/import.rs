@@ -0,0 +1,645 @@
+use anyhow::{Context, Error, Result};
+use rocket::fs::TempFile;
+use rocket::serde::json::{self, Value};
+use rocket::serde::Serialize;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+use crate::bibliography::Bibliography;
+use crate::duplicate::{self, Identifier};
+use crate::item::NewItemData;
+
+/// Bibliography formats which can be imported.
+#[derive(FromFormField, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Bibtex,
+    Ris,
+    #[field(value = "csl-json")]
+    CslJson,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format> {
+        match s.to_lowercase().as_str() {
+            "bib" | "bibtex" => Ok(Format::Bibtex),
+            "ris" => Ok(Format::Ris),
+            "json" | "csl-json" | "csljson" => Ok(Format::CslJson),
+            _ => Err(Error::msg(format!("Unknown format: {}", s))),
+        }
+    }
+}
+
+impl Format {
+    /// Guesses the format from the content of a file.
+    pub fn detect(content: &str) -> Option<Format> {
+        let content = content.trim_start_matches('\u{feff}').trim_start();
+        if content.starts_with('[') || content.starts_with('{') {
+            Some(Format::CslJson)
+        } else if content.lines().any(|l| l.starts_with("TY  -")) {
+            Some(Format::Ris)
+        } else if content.contains('@') {
+            Some(Format::Bibtex)
+        } else {
+            None
+        }
+    }
+}
+
+/// A single paper read from a bibliography file.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct Entry {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub year: Option<String>,
+    pub venue: Option<String>,
+    pub summary: Option<String>,
+    pub doi: Option<String>,
+    pub arxiv: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(FromForm)]
+pub struct UploadData<'r> {
+    pub file: TempFile<'r>,
+    pub format: Option<Format>,
+}
+
+#[derive(FromForm)]
+pub struct ImportData {
+    pub entries: Vec<ImportEntryData>,
+}
+
+#[derive(FromForm)]
+pub struct ImportEntryData {
+    pub title: String,
+    pub markdown: String,
+    pub bib: Bibliography,
+    /// unchecked entries are skipped
+    pub add: bool,
+}
+
+pub(crate) fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+pub(crate) fn year_of(date: &str) -> Option<String> {
+    let year: String = date
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    (year.len() == 4).then_some(year)
+}
+
+// "Last, First" as used by BibTeX and RIS
+fn author_name(name: &str) -> String {
+    match name.split_once(',') {
+        Some((last, first)) if !first.trim().is_empty() => {
+            format!("{} {}", first.trim(), last.trim())
+        }
+        Some((last, _)) => last.trim().to_string(),
+        None => name.trim().to_string(),
+    }
+}
+
+impl Entry {
+    // identifiers hidden in links, e.g. arXiv abstract pages
+    pub(crate) fn complete(mut self) -> Entry {
+        if let Some(url) = &self.url {
+            match duplicate::identify(url) {
+                Some(Identifier::Arxiv(id)) if self.arxiv.is_none() => self.arxiv = Some(id),
+                Some(Identifier::Doi(doi)) if self.doi.is_none() => self.doi = Some(doi),
+                _ => (),
+            }
+        }
+        // links which only repeat the DOI or arXiv ID are dropped
+        if let Some(Identifier::Arxiv(_) | Identifier::Doi(_)) =
+            self.url.as_deref().and_then(duplicate::identify)
+        {
+            self.url = None;
+        }
+        self
+    }
+
+    pub fn bibliography(&self) -> Bibliography {
+        Bibliography {
+            authors: (!self.authors.is_empty()).then(|| self.authors.join(", ")),
+            year: self.year.as_deref().and_then(|y| y.parse().ok()),
+            venue: self.venue.clone(),
+            doi: self.doi.clone(),
+            arxiv_id: self.arxiv.clone(),
+            url: self.url.clone(),
+        }
+    }
+
+    /// Free-form notes in the layout of the new item template.
+    pub fn markdown(&self) -> String {
+        match &self.summary {
+            Some(summary) => format!("**Topic:**\n\n{}\n", summary),
+            None => String::new(),
+        }
+    }
+
+    pub fn to_item(&self) -> Result<NewItemData> {
+        let markdown = self.markdown();
+        let bib = self.bibliography().normalized()?;
+        Ok(NewItemData {
+            title: self.title.clone(),
+            html: bib.render(&markdown)?,
+            markdown,
+            bib,
+            tags: String::new(),
+            confirm: true,
+        })
+    }
+}
+
+/// Parses all entries of a bibliography, guessing the format if none is given.
+pub fn parse(content: &str, format: Option<Format>) -> Result<Vec<Entry>> {
+    let format = format
+        .or_else(|| Format::detect(content))
+        .ok_or(Error::msg("Could not detect the format of the file."))?;
+    let entries = match format {
+        Format::Bibtex => parse_bibtex(content)?,
+        Format::Ris => parse_ris(content),
+        Format::CslJson => parse_csl_json(content)?,
+    };
+    Ok(entries.into_iter().map(Entry::complete).collect())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// BibTeX
+///////////////////////////////////////////////////////////////////////////////
+
+// reads up to the (unescaped) `end` character on the same brace level
+fn bibtex_group(chars: &mut Peekable<Chars>, end: char) -> String {
+    let mut value = String::new();
+    let mut depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                value.push(c);
+                if let Some(escaped) = chars.next() {
+                    value.push(escaped);
+                }
+                continue;
+            }
+            c if c == end && depth == 0 => break,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => (),
+        }
+        value.push(c);
+    }
+    value
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+// field values may be braced, quoted, bare words or @string macros and concatenations of those
+// with `#`
+fn bibtex_value(chars: &mut Peekable<Chars>, macros: &HashMap<String, String>) -> String {
+    let mut value = String::new();
+    loop {
+        skip_whitespace(chars);
+        match chars.next() {
+            Some('{') => value.push_str(&bibtex_group(chars, '}')),
+            Some('"') => value.push_str(&bibtex_group(chars, '"')),
+            Some(c) => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c == ',' || c == '#' || c.is_whitespace() {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                value.push_str(macros.get(&word.to_lowercase()).unwrap_or(&word));
+            }
+            None => break,
+        }
+        skip_whitespace(chars);
+        if chars.peek() != Some(&'#') {
+            break;
+        }
+        chars.next();
+    }
+    value
+}
+
+fn bibtex_fields(body: &str, macros: &HashMap<String, String>) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut chars = body.chars().peekable();
+    loop {
+        let mut name = String::new();
+        for c in chars.by_ref() {
+            if c == '=' {
+                break;
+            }
+            name.push(c);
+        }
+        let name = name.trim_matches(|c: char| c == ',' || c.is_whitespace());
+        if name.is_empty() || chars.peek().is_none() {
+            break;
+        }
+        fields.insert(name.to_lowercase(), bibtex_value(&mut chars, macros));
+    }
+    fields
+}
+
+fn accented(accent: char, letter: char) -> Option<char> {
+    let table: &[(char, &str, &str)] = &[
+        ('"', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+        ('\'', "aeiouycnszAEIOUYCNSZ", "áéíóúýćńśźÁÉÍÓÚÝĆŃŚŹ"),
+        ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+        ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+        ('~', "anoANO", "ãñõÃÑÕ"),
+    ];
+    let (_, plain, marked) = table.iter().find(|(a, _, _)| *a == accent)?;
+    let pos = plain.chars().position(|c| c == letter)?;
+    marked.chars().nth(pos)
+}
+
+/// Plain text of a (simple) LaTeX string, e.g. `{\"U}ber {GANs}` becomes `Über GANs`.
+fn latex_to_text(latex: &str) -> String {
+    let mut text = String::new();
+    let mut chars = latex.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => (),
+            '~' => text.push(' '),
+            '\\' => match chars.next() {
+                Some(accent @ ('"' | '\'' | '`' | '^' | '~')) => {
+                    let braced = chars.peek() == Some(&'{');
+                    if braced {
+                        chars.next();
+                    }
+                    if let Some(letter) = chars.next() {
+                        text.push(accented(accent, letter).unwrap_or(letter));
+                    }
+                    if braced && chars.peek() == Some(&'}') {
+                        chars.next();
+                    }
+                }
+                Some(c) if c.is_alphabetic() => {
+                    // drop commands like \emph or \textbf but keep their argument
+                    while chars.peek().is_some_and(|c| c.is_alphabetic()) {
+                        chars.next();
+                    }
+                }
+                Some(c) => text.push(c),
+                None => (),
+            },
+            _ => text.push(c),
+        }
+    }
+    text.replace("---", "—")
+        .replace("--", "–")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// authors are separated by "and" outside of braces
+fn bibtex_authors(authors: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for word in authors.split_whitespace() {
+        if depth == 0 && word == "and" {
+            names.push(std::mem::take(&mut current));
+            continue;
+        }
+        depth += word.matches('{').count() as i32 - word.matches('}').count() as i32;
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    names.push(current);
+    names
+        .iter()
+        .map(|name| author_name(&latex_to_text(name)))
+        .filter(|name| !name.is_empty() && name != "others")
+        .collect()
+}
+
+fn bibtex_entry(fields: HashMap<String, String>) -> Entry {
+    let field = |name: &str| fields.get(name).and_then(|v| non_empty(&latex_to_text(v)));
+    let arxiv = match field("archiveprefix").or_else(|| field("eprinttype")) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("arxiv") => {
+            field("eprint").and_then(|id| duplicate::clean_arxiv(&id))
+        }
+        _ => None,
+    };
+    Entry {
+        title: field("title").unwrap_or_default(),
+        authors: fields
+            .get("author")
+            .map(|a| bibtex_authors(a))
+            .unwrap_or_default(),
+        year: field("year").or_else(|| field("date").and_then(|d| year_of(&d))),
+        venue: field("journal")
+            .or_else(|| field("booktitle"))
+            .or_else(|| field("journaltitle"))
+            .or_else(|| field("howpublished"))
+            .or_else(|| field("publisher")),
+        summary: field("abstract"),
+        // DOIs and links must not lose their underscores or tildes
+        doi: fields.get("doi").and_then(|d| duplicate::clean_doi(d)),
+        arxiv,
+        url: fields.get("url").and_then(|u| non_empty(u)),
+    }
+}
+
+pub fn parse_bibtex(content: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut macros = HashMap::new();
+    let mut rest = content;
+    while let Some(start) = rest.find('@') {
+        rest = &rest[start + 1..];
+        let open = rest
+            .find(['{', '('])
+            .ok_or(Error::msg("Missing opening brace of BibTeX entry."))?;
+        let kind = rest[..open].trim().to_lowercase();
+        let closing = if rest[open..].starts_with('{') {
+            '}'
+        } else {
+            ')'
+        };
+        let body = &rest[open + 1..];
+
+        let mut depth = 0;
+        let end = body
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '{' => depth += 1,
+                    '}' if depth > 0 => depth -= 1,
+                    c if c == closing => return true,
+                    _ => (),
+                }
+                false
+            })
+            .map(|(i, _)| i)
+            .ok_or_else(|| Error::msg(format!("Unterminated BibTeX entry @{}.", kind)))?;
+        rest = &body[end + 1..];
+
+        let body = &body[..end];
+        match kind.as_str() {
+            "comment" | "preamble" => (),
+            "string" => macros.extend(bibtex_fields(body, &macros)),
+            // fields follow the citation key
+            _ => {
+                let fields = body.split_once(',').map_or("", |(_, fields)| fields);
+                entries.push(bibtex_entry(bibtex_fields(fields, &macros)));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// RIS
+///////////////////////////////////////////////////////////////////////////////
+
+fn ris_entry(fields: &[(String, String)]) -> Entry {
+    let first = |tags: &[&str]| {
+        tags.iter().find_map(|tag| {
+            fields
+                .iter()
+                .find(|(t, v)| t == tag && !v.is_empty())
+                .map(|(_, v)| v.clone())
+        })
+    };
+    Entry {
+        title: first(&["TI", "T1", "CT"]).unwrap_or_default(),
+        authors: fields
+            .iter()
+            .filter(|(t, _)| t == "AU" || t == "A1")
+            .map(|(_, v)| author_name(v))
+            .collect(),
+        year: first(&["PY", "Y1", "DA"]).and_then(|d| year_of(&d)),
+        venue: first(&["JF", "JO", "T2", "BT", "JA", "PB"]),
+        summary: first(&["AB", "N2"]),
+        doi: first(&["DO"]).and_then(|d| duplicate::clean_doi(&d)),
+        arxiv: None,
+        url: first(&["UR"]),
+    }
+}
+
+pub fn parse_ris(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut fields: Vec<(String, String)> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim_end();
+        let tagged = line.len() >= 5
+            && line.is_char_boundary(2)
+            && line[..2].chars().all(|c| c.is_ascii_alphanumeric())
+            && line[2..].starts_with("  -");
+        if !tagged {
+            // continuation of a multi-line value, e.g. an abstract
+            if let Some((_, value)) = fields.last_mut() {
+                if !line.trim().is_empty() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            }
+            continue;
+        }
+
+        let tag = &line[..2];
+        let value = line[5..].trim();
+        match tag {
+            // a record missing its "ER" line ends with the next one
+            "TY" | "ER" if !fields.is_empty() => {
+                entries.push(ris_entry(&std::mem::take(&mut fields)))
+            }
+            "TY" | "ER" => (),
+            _ => fields.push((tag.to_string(), value.to_string())),
+        }
+    }
+    if !fields.is_empty() {
+        entries.push(ris_entry(&fields));
+    }
+    entries
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// CSL-JSON
+///////////////////////////////////////////////////////////////////////////////
+
+fn csl_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => non_empty(s),
+        Value::Number(n) => Some(n.to_string()),
+        // some exporters use arrays for container titles
+        Value::Array(values) => values.iter().find_map(csl_text),
+        _ => None,
+    }
+}
+
+pub(crate) fn csl_entry(value: &Value) -> Entry {
+    let field = |name: &str| value.get(name).and_then(csl_text);
+    let authors = value
+        .get("author")
+        .and_then(Value::as_array)
+        .map(|authors| {
+            authors
+                .iter()
+                .filter_map(|a| {
+                    let part = |name: &str| a.get(name).and_then(csl_text);
+                    match (part("given"), part("family")) {
+                        (Some(given), Some(family)) => Some(format!("{} {}", given, family)),
+                        (None, Some(family)) => Some(family),
+                        _ => part("literal"),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let year = value.get("issued").and_then(|issued| {
+        issued
+            .pointer("/date-parts/0/0")
+            .and_then(csl_text)
+            .or_else(|| issued.get("raw").and_then(csl_text))
+            .or_else(|| issued.get("literal").and_then(csl_text))
+            .and_then(|d| year_of(&d))
+    });
+    let arxiv = match field("archive") {
+        Some(archive) if archive.eq_ignore_ascii_case("arxiv") => {
+            field("number").and_then(|n| duplicate::clean_arxiv(&n))
+        }
+        _ => None,
+    };
+    Entry {
+        title: field("title").unwrap_or_default(),
+        authors,
+        year,
+        venue: field("container-title")
+            .or_else(|| field("event"))
+            .or_else(|| field("publisher")),
+        summary: field("abstract"),
+        doi: field("DOI").and_then(|d| duplicate::clean_doi(&d)),
+        arxiv,
+        url: field("URL"),
+    }
+}
+
+pub fn parse_csl_json(content: &str) -> Result<Vec<Entry>> {
+    let value: Value =
+        json::from_str(content.trim_start_matches('\u{feff}')).context("Invalid CSL-JSON file.")?;
+    match value {
+        Value::Array(values) => Ok(values.iter().map(csl_entry).collect()),
+        Value::Object(_) => Ok(vec![csl_entry(&value)]),
+        _ => Err(Error::msg(
+            "CSL-JSON must be an object or a list of objects.",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bibtex_nested_braces_and_quotes() {
+        let entries = parse_bibtex(
+            r#"@article{doe2020,
+  title = {The {GAN} {\"U}bersicht},
+  author = "Doe, Jane and {Research Group}",
+  journal = {J. {ML} Res.},
+  year = 2020,
+  eprint = {2001.01234}, archivePrefix = {arXiv},
+  doi = {10.1000/a_b}
+}"#,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.title, "The GAN Übersicht");
+        assert_eq!(entry.authors, ["Jane Doe", "Research Group"]);
+        assert_eq!(entry.venue.as_deref(), Some("J. ML Res."));
+        assert_eq!(entry.year.as_deref(), Some("2020"));
+        assert_eq!(entry.arxiv.as_deref(), Some("2001.01234"));
+        assert_eq!(entry.doi.as_deref(), Some("10.1000/a_b"));
+    }
+
+    #[test]
+    fn bibtex_strings_and_comments() {
+        let entries = parse_bibtex(
+            r#"@comment{an old entry: @article{old, title = {Old}}}
+@preamble{"\newcommand{\noop}[1]{}"}
+@string{neurips = "Advances in Neural Information Processing Systems"}
+@inproceedings{new,
+  title = {New},
+  booktitle = neurips # " 33",
+}"#,
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "New");
+        assert_eq!(
+            entries[0].venue.as_deref(),
+            Some("Advances in Neural Information Processing Systems 33")
+        );
+    }
+
+    #[test]
+    fn bibtex_multi_line_fields() {
+        let entries = parse_bibtex(
+            "@misc{long,\n  title = {A very\n    long title},\n  abstract = \"First line.\n    Second line.\"\n}\n",
+        )
+        .unwrap();
+        assert_eq!(entries[0].title, "A very long title");
+        assert_eq!(
+            entries[0].summary.as_deref(),
+            Some("First line. Second line.")
+        );
+    }
+
+    #[test]
+    fn ris_repeated_authors() {
+        let entries = parse_ris(
+            "TY  - JOUR\nAU  - Doe, Jane\nAU  - Roe, Richard\nA1  - Poe, Edgar\nTI  - Title\nPY  - 2019/05/01\nER  - \n",
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "Title");
+        assert_eq!(entries[0].authors, ["Jane Doe", "Richard Roe", "Edgar Poe"]);
+        assert_eq!(entries[0].year.as_deref(), Some("2019"));
+    }
+
+    #[test]
+    fn ris_missing_end_of_record() {
+        let entries = parse_ris(
+            "TY  - JOUR\nTI  - First\nTY  - CONF\nTI  - Second\nER  - \nTY  - GEN\nTI  - Third\n",
+        );
+        let titles: Vec<_> = entries.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["First", "Second", "Third"]);
+    }
+
+    #[test]
+    fn csl_json_dates_and_literal_authors() {
+        let entries = parse_csl_json(
+            r#"[
+  {"title": "One", "issued": {"date-parts": [[2021, 3, 1]]},
+   "author": [{"given": "Jane", "family": "Doe"}, {"literal": "The Consortium"}]},
+  {"title": "Two", "issued": {"date-parts": [["2018"]]}, "author": [{"family": "Roe"}]}
+]"#,
+        )
+        .unwrap();
+        assert_eq!(entries[0].authors, ["Jane Doe", "The Consortium"]);
+        assert_eq!(entries[0].year.as_deref(), Some("2021"));
+        assert_eq!(entries[1].authors, ["Roe"]);
+        assert_eq!(entries[1].year.as_deref(), Some("2018"));
+    }
+}
@@ -7,9 +7,34 @@ extern crate rocket;
 
 mod db;
 
+pub mod chart;
+pub mod duplicate;
+pub mod export;
+pub mod import;
+pub mod lookup;
 pub mod mail;
+pub mod markdown;
 
+pub use db::attachment;
+pub use db::attendance;
+pub use db::bibliography;
+pub use db::comment;
+pub use db::endorsement;
+pub use db::expiry;
+pub use db::fairness;
 pub use db::item;
+pub use db::limits;
+pub use db::meeting;
+pub use db::moderation;
+pub use db::notification;
+pub use db::presenter;
+pub use db::recommendation;
+pub use db::revision;
+pub use db::search;
+pub use db::stats;
+pub use db::status;
+pub use db::tag;
+pub use db::track;
 pub use db::user;
 pub use db::vote;
 pub use db::DbConn;
@@ -0,0 +1,192 @@
+use anyhow::{Context, Error, Result};
+use reqwest::Url;
+use rocket::serde::json::{self, Value};
+use rocket::serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::duplicate;
+use crate::import::{self, Entry};
+use crate::item::NewItemData;
+
+/// Metadata APIs used to prefill items, configured in `Rocket.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde", default)]
+pub struct LookupConfig {
+    /// base URL of a Crossref compatible API, queried with `/works/<doi>`
+    pub lookup_crossref_url: String,
+    /// base URL of an arXiv compatible API, queried with `/query?id_list=<id>`
+    pub lookup_arxiv_url: String,
+    /// in seconds
+    pub lookup_timeout: u64,
+}
+
+impl Default for LookupConfig {
+    fn default() -> LookupConfig {
+        LookupConfig {
+            lookup_crossref_url: "https://api.crossref.org".to_string(),
+            lookup_arxiv_url: "https://export.arxiv.org/api".to_string(),
+            lookup_timeout: 10,
+        }
+    }
+}
+
+/// New item filled from the metadata of a paper, see `/lookup`.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Prefill {
+    #[serde(flatten)]
+    pub item: NewItemData,
+    /// abstract of the paper
+    pub summary: Option<String>,
+}
+
+impl Prefill {
+    pub fn from_entry(entry: Entry) -> Result<Prefill> {
+        Ok(Prefill {
+            item: entry.to_item()?,
+            summary: entry.summary,
+        })
+    }
+}
+
+fn base_url(url: &str) -> Result<Url> {
+    Url::parse(url).with_context(|| format!("Invalid lookup URL {}", url))
+}
+
+async fn get(config: &LookupConfig, url: Url) -> Result<String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.lookup_timeout))
+        .user_agent(concat!("prank/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("Failed to create http client.")?;
+    let response = client
+        .get(url.clone())
+        .send()
+        .await
+        .with_context(|| format!("Could not reach {}", url))?;
+    if !response.status().is_success() {
+        return Err(Error::msg(format!(
+            "{} answered with {}",
+            url,
+            response.status()
+        )));
+    }
+    response
+        .text()
+        .await
+        .with_context(|| format!("Invalid response from {}", url))
+}
+
+// Crossref abstracts are JATS XML, e.g. "<jats:p>...</jats:p>"
+fn strip_tags(text: &str) -> String {
+    let mut plain = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                plain.push(' ');
+            }
+            c if !in_tag => plain.push(c),
+            _ => (),
+        }
+    }
+    plain.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Metadata of the paper with the given DOI.
+pub async fn crossref(config: &LookupConfig, doi: &str) -> Result<Entry> {
+    let doi = duplicate::clean_doi(doi).ok_or(Error::msg("Empty DOI."))?;
+    let mut url = base_url(&config.lookup_crossref_url)?;
+    url.path_segments_mut()
+        .map_err(|_| Error::msg("Invalid Crossref URL."))?
+        .pop_if_empty()
+        .extend(["works", &doi]);
+    let body = get(config, url).await?;
+    let value: Value = json::from_str(&body).context("Invalid response from Crossref.")?;
+    let message = value
+        .get("message")
+        .ok_or(Error::msg("Invalid response from Crossref."))?;
+
+    let mut entry = import::csl_entry(message);
+    entry.summary = entry.summary.map(|s| strip_tags(&s));
+    entry.doi = entry.doi.or(Some(doi));
+    if entry.title.is_empty() {
+        return Err(Error::msg(format!(
+            "No title found for DOI {}",
+            entry.doi.unwrap_or_default()
+        )));
+    }
+    Ok(entry.complete())
+}
+
+fn arxiv_entry(xml: &str) -> Result<Entry> {
+    let doc = roxmltree::Document::parse(xml).context("Invalid response from arXiv.")?;
+    let entry = doc
+        .descendants()
+        .find(|n| n.has_tag_name("entry"))
+        .ok_or(Error::msg("Paper not found on arXiv."))?;
+    let child = |name: &str| {
+        entry
+            .children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .and_then(import::non_empty)
+            .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+    };
+    let id = child("id").ok_or(Error::msg("Paper not found on arXiv."))?;
+
+    Ok(Entry {
+        title: child("title").unwrap_or_default(),
+        authors: entry
+            .children()
+            .filter(|n| n.has_tag_name("author"))
+            .filter_map(|a| a.children().find(|n| n.has_tag_name("name")))
+            .filter_map(|n| n.text())
+            .map(|name| name.trim().to_string())
+            .collect(),
+        year: child("published").and_then(|d| import::year_of(&d)),
+        venue: child("journal_ref"),
+        summary: child("summary"),
+        doi: child("doi").and_then(|d| duplicate::clean_doi(&d)),
+        arxiv: None,
+        url: Some(id),
+    }
+    .complete())
+}
+
+/// Metadata of the paper with the given arXiv ID.
+pub async fn arxiv(config: &LookupConfig, id: &str) -> Result<Entry> {
+    let id = duplicate::clean_arxiv(id).ok_or(Error::msg("Empty arXiv ID."))?;
+    if !duplicate::is_arxiv_id(&id) {
+        return Err(Error::msg(format!("{} is not a valid arXiv ID.", id)));
+    }
+    let mut url = base_url(&config.lookup_arxiv_url)?;
+    url.path_segments_mut()
+        .map_err(|_| Error::msg("Invalid arXiv URL."))?
+        .pop_if_empty()
+        .push("query");
+    url.query_pairs_mut().append_pair("id_list", &id);
+    let entry = arxiv_entry(&get(config, url).await?)?;
+    // unknown IDs are answered with an error entry
+    if entry.title.is_empty() || entry.arxiv.is_none() {
+        return Err(Error::msg(format!("Paper {} not found on arXiv.", id)));
+    }
+    Ok(entry)
+}
+
+/// Looks up a DOI if given, otherwise an arXiv ID.
+pub async fn find(
+    config: &LookupConfig,
+    doi: Option<&str>,
+    arxiv_id: Option<&str>,
+) -> Result<Entry> {
+    let doi = doi.and_then(import::non_empty);
+    let arxiv_id = arxiv_id.and_then(import::non_empty);
+    match (doi, arxiv_id) {
+        (Some(doi), _) => crossref(config, &doi).await,
+        (None, Some(id)) => arxiv(config, &id).await,
+        (None, None) => Err(Error::msg("Enter a DOI or an arXiv ID to look up.")),
+    }
+}
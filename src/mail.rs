@@ -2,6 +2,7 @@ use lettre::message::{header, MultiPart, SinglePart};
 use lettre::{transport::smtp::authentication::Credentials, Message, SmtpTransport, Transport};
 
 use crate::item::{Item, ItemFormat};
+use crate::markdown::escape_html;
 use anyhow::{Context, Result};
 
 fn format_comment(comment: &Option<String>, item_format: ItemFormat) -> String {
@@ -18,8 +19,20 @@ fn format_comment(comment: &Option<String>, item_format: ItemFormat) -> String {
     }
 }
 
+fn format_presenter(presenter: &Option<String>, item_format: ItemFormat) -> String {
+    match presenter {
+        Some(p) => match item_format {
+            ItemFormat::HTML => format!("<p>Presented by {}</p>\n", escape_html(p)),
+            ItemFormat::Markdown => format!("Presented by {}\n\n", p),
+        },
+        None => String::new(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn send(
     item: &Item,
+    presenter: Option<String>,
     from: String,
     to: String,
     comment: Option<String>,
@@ -42,7 +55,8 @@ pub fn send(
                     SinglePart::builder()
                         .header(header::ContentType::parse("text/markdown").unwrap())
                         .body(format!(
-                            "{}{}",
+                            "{}{}{}",
+                            format_presenter(&presenter, ItemFormat::Markdown),
                             item.format(ItemFormat::Markdown),
                             format_comment(&comment, ItemFormat::Markdown)
                         )),
@@ -51,7 +65,8 @@ pub fn send(
                     SinglePart::builder()
                         .header(header::ContentType::TEXT_HTML)
                         .body(format!(
-                            "{}{}",
+                            "{}{}{}",
+                            format_presenter(&presenter, ItemFormat::HTML),
                             item.format(ItemFormat::HTML),
                             format_comment(&comment, ItemFormat::HTML)
                         )),
@@ -8,15 +8,11 @@ pub fn markdown_to_html(md: &str) -> Result<String> {
     let root = parse_document(&arena, md, &ComrakOptions::default());
 
     fn filter_images<'a>(node: &'a AstNode<'a>) {
-        let is_image = |node: &AstNode| -> bool {
-            match node.data.borrow().value {
-                NodeValue::Image(_) => true,
-                _ => false,
-            }
-        };
+        let is_image =
+            |node: &AstNode| -> bool { matches!(node.data.borrow().value, NodeValue::Image(_)) };
 
         let mut children = node.children().peekable();
-        if is_image(node) || (children.peek().is_some() && children.all(|n| is_image(n))) {
+        if is_image(node) || (children.peek().is_some() && children.all(is_image)) {
             node.detach();
         }
 
@@ -34,3 +30,23 @@ pub fn markdown_to_html(md: &str) -> Result<String> {
     String::from_utf8(html)
         .context("Error rendering markdown.")
 }
+
+/// Escapes all markdown syntax in `text`, e.g. to use it as plain text in generated markdown.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii_punctuation() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes `text` for use in html, including attribute values.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}